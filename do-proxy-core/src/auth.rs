@@ -0,0 +1,804 @@
+//! Authentication of callers.
+//!
+//! A caller can send a [`Principal`] along with its requests, signed with a
+//! key from a [`Keyring`] shared with the object. Objects that have a keyring
+//! verify the signature before handling the request and reject requests
+//! without a valid principal with [`crate::Error::Unauthenticated`].
+//!
+//! Signatures are HMAC-SHA256 over the object's binding, the object the
+//! request is sent to, a digest of the request envelope, the principal and the
+//! time the signature expires at. A signed principal is only valid for the
+//! request it was sent with, so it can't be replayed with another request,
+//! against another object, nor after it expired. Proxies hold on to a
+//! [`Signer`] and sign the principal again for every request.
+//!
+//! Which commands a principal may send is decided by the object, for example
+//! with a [`Policy`].
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{codec, transport::EnvelopeVersion, ObjectApi};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The command name of init requests, for [`Policy`] and audit records. The
+/// same as the JSON-RPC init method.
+pub const INIT_COMMAND: &str = "do_proxy.init";
+
+/// How long signatures are valid for, unless set with [`Keyring::with_ttl`].
+pub const DEFAULT_SIGNATURE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// The caller a request is sent on behalf of.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Principal {
+    /// Who the caller is, e.g. a user or service ID.
+    pub subject: String,
+    /// The roles granted to the caller.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+}
+
+impl Principal {
+    pub fn new(subject: impl Into<String>) -> Self {
+        Self {
+            subject: subject.into(),
+            roles: Vec::new(),
+        }
+    }
+
+    /// Grants the principal `role`.
+    pub fn with_role(mut self, role: impl Into<String>) -> Self {
+        self.roles.push(role.into());
+        self
+    }
+
+    /// Returns `true` if the principal was granted `role`.
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|granted| granted == role)
+    }
+}
+
+/// The object a principal is signed for.
+///
+/// Callers that reach the object through a stub know its ID. Callers that
+/// reach it through a gateway only know its name, which the object resolves
+/// to its ID in its own namespace.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub enum ObjectRef {
+    /// The object with the given name.
+    Name(String),
+    /// The object with the given hex ID.
+    Id(String),
+}
+
+/// A [`Principal`] signed by the caller, as it's sent in a request envelope.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SignedPrincipal {
+    pub principal: Principal,
+    /// The object the principal may be sent to.
+    pub object: ObjectRef,
+    /// The ID of the key in the [`Keyring`] the principal was signed with.
+    pub key_id: String,
+    /// When the signature expires, in seconds since the Unix epoch.
+    pub expires_at: u64,
+    /// The hex-encoded HMAC-SHA256 signature.
+    pub signature: String,
+}
+
+/// The keys principals are signed and verified with.
+///
+/// A keyring has any number of keys, identified by an ID that is sent along
+/// with every signature, and signs with one of them. To rotate keys without
+/// rejecting requests in flight, first add the new key to every object's
+/// keyring, then sign with it, then remove the old key.
+///
+/// Keyrings are usually read from a secret with [`Keyring::parse`], e.g. one
+/// set with `wrangler secret put DO_PROXY_KEYS`.
+#[derive(Clone)]
+pub struct Keyring {
+    signing_key: Option<String>,
+    keys: BTreeMap<String, Vec<u8>>,
+    ttl: Duration,
+}
+
+impl Default for Keyring {
+    fn default() -> Self {
+        Self {
+            signing_key: None,
+            keys: BTreeMap::new(),
+            ttl: DEFAULT_SIGNATURE_TTL,
+        }
+    }
+}
+
+impl Keyring {
+    /// Creates a keyring without any keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how long signatures made with the keyring are valid for. Defaults
+    /// to [`DEFAULT_SIGNATURE_TTL`].
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Parses a keyring from a comma-separated list of `{key_id}:{secret}`
+    /// pairs. The first key is used for signing.
+    ///
+    /// ```ignore
+    /// let keyring = Keyring::parse("2024-06:new-secret,2024-01:old-secret")?;
+    /// ```
+    pub fn parse(keys: &str) -> Result<Self, crate::Error> {
+        let mut keyring = Self::new();
+
+        for (index, key) in keys.split(',').map(str::trim).enumerate() {
+            let Some((id, secret)) = key
+                .split_once(':')
+                .filter(|(id, secret)| !id.is_empty() && !secret.is_empty())
+            else {
+                return Err(crate::Error::worker(format!(
+                    "invalid keyring entry {index}, expected `{{key_id}}:{{secret}}`"
+                )));
+            };
+
+            keyring = if index == 0 {
+                keyring.with_signing_key(id, secret)
+            } else {
+                keyring.with_key(id, secret)
+            };
+        }
+
+        Ok(keyring)
+    }
+
+    /// Reads a keyring from the secret `name`, see [`Keyring::parse`].
+    #[cfg(feature = "worker")]
+    pub fn from_env(env: &worker::Env, name: &str) -> Result<Self, crate::Error> {
+        Self::parse(&env.secret(name)?.to_string())
+    }
+
+    /// Adds a key that principals are verified with.
+    pub fn with_key(mut self, key_id: impl Into<String>, secret: impl AsRef<[u8]>) -> Self {
+        self.keys.insert(key_id.into(), secret.as_ref().to_vec());
+        self
+    }
+
+    /// Adds a key and signs principals with it from now on.
+    pub fn with_signing_key(self, key_id: impl Into<String>, secret: impl AsRef<[u8]>) -> Self {
+        let key_id = key_id.into();
+        let mut keyring = self.with_key(key_id.clone(), secret);
+        keyring.signing_key = Some(key_id);
+        keyring
+    }
+
+    /// Signs `principal` for the request with the digest `request_digest`,
+    /// sent to `object` bound under `binding`, see
+    /// [`crate::codec::request_digest`]. The signature expires after the
+    /// keyring's TTL, see [`Keyring::with_ttl`].
+    pub fn sign(
+        &self,
+        binding: &str,
+        object: ObjectRef,
+        request_digest: &str,
+        principal: Principal,
+    ) -> Result<SignedPrincipal, crate::Error> {
+        self.sign_at(binding, object, request_digest, principal, unix_now())
+    }
+
+    /// Signs `principal` as if it was `now` seconds since the Unix epoch.
+    pub fn sign_at(
+        &self,
+        binding: &str,
+        object: ObjectRef,
+        request_digest: &str,
+        principal: Principal,
+        now: u64,
+    ) -> Result<SignedPrincipal, crate::Error> {
+        let key_id = self
+            .signing_key
+            .clone()
+            .ok_or_else(|| crate::Error::worker("the keyring has no signing key"))?;
+        let expires_at = now.saturating_add(self.ttl.as_secs());
+        let signature = hex::encode(
+            self.mac(
+                &key_id,
+                binding,
+                &object,
+                request_digest,
+                expires_at,
+                &principal,
+            )?
+            .finalize()
+            .into_bytes(),
+        );
+
+        Ok(SignedPrincipal {
+            principal,
+            object,
+            key_id,
+            expires_at,
+            signature,
+        })
+    }
+
+    /// Verifies a principal sent with the request with the digest
+    /// `request_digest` to an object bound under `binding`, rejecting it once
+    /// its signature expired.
+    ///
+    /// The signature covers [`SignedPrincipal::object`], but whether that's
+    /// the object verifying it is up to the caller to check.
+    pub fn verify(
+        &self,
+        binding: &str,
+        request_digest: &str,
+        signed: &SignedPrincipal,
+    ) -> Result<Principal, crate::Error> {
+        self.verify_at(binding, request_digest, signed, unix_now())
+    }
+
+    /// Verifies a principal as if it was `now` seconds since the Unix epoch.
+    pub fn verify_at(
+        &self,
+        binding: &str,
+        request_digest: &str,
+        signed: &SignedPrincipal,
+        now: u64,
+    ) -> Result<Principal, crate::Error> {
+        if !self.keys.contains_key(&signed.key_id) {
+            return Err(crate::Error::Unauthenticated {
+                reason: format!("unknown key `{}`", signed.key_id),
+            });
+        }
+
+        let invalid = || crate::Error::Unauthenticated {
+            reason: "invalid signature".to_string(),
+        };
+        let signature = hex::decode(&signed.signature).map_err(|_| invalid())?;
+        self.mac(
+            &signed.key_id,
+            binding,
+            &signed.object,
+            request_digest,
+            signed.expires_at,
+            &signed.principal,
+        )?
+        .verify_slice(&signature)
+        .map_err(|_| invalid())?;
+
+        if now >= signed.expires_at {
+            return Err(crate::Error::Unauthenticated {
+                reason: "the signature expired".to_string(),
+            });
+        }
+
+        Ok(signed.principal.clone())
+    }
+
+    fn mac(
+        &self,
+        key_id: &str,
+        binding: &str,
+        object: &ObjectRef,
+        request_digest: &str,
+        expires_at: u64,
+        principal: &Principal,
+    ) -> Result<HmacSha256, crate::Error> {
+        let principal = serde_json::to_vec(principal).map_err(|err| crate::Error::encode(&err))?;
+        let mut mac = HmacSha256::new_from_slice(&self.keys[key_id])
+            .map_err(|err| crate::Error::worker(err.to_string()))?;
+        mac.update(binding.as_bytes());
+        mac.update(b"\n");
+        mac.update(key_id.as_bytes());
+        mac.update(b"\n");
+        match object {
+            ObjectRef::Name(name) => mac.update(format!("name:{name}").as_bytes()),
+            ObjectRef::Id(id) => mac.update(format!("id:{id}").as_bytes()),
+        }
+        mac.update(b"\n");
+        mac.update(request_digest.as_bytes());
+        mac.update(b"\n");
+        mac.update(expires_at.to_string().as_bytes());
+        mac.update(b"\n");
+        mac.update(&principal);
+
+        Ok(mac)
+    }
+}
+
+/// Only the key IDs are shown, never the secrets.
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyring")
+            .field("signing_key", &self.signing_key)
+            .field("keys", &self.keys.keys().collect::<Vec<_>>())
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+/// A principal and the keyring it's signed with. Proxies keep a signer rather
+/// than a [`SignedPrincipal`] and sign every request as it's sent, so a
+/// long-lived proxy or session never sends an expired signature.
+#[derive(Debug, Clone)]
+pub struct Signer {
+    principal: Principal,
+    keyring: Keyring,
+}
+
+impl Signer {
+    /// Creates a signer, failing if `keyring` has no signing key.
+    pub fn new(principal: Principal, keyring: &Keyring) -> Result<Self, crate::Error> {
+        if keyring.signing_key.is_none() {
+            return Err(crate::Error::worker("the keyring has no signing key"));
+        }
+
+        Ok(Self {
+            principal,
+            keyring: keyring.clone(),
+        })
+    }
+
+    /// The principal requests are sent on behalf of.
+    pub fn principal(&self) -> &Principal {
+        &self.principal
+    }
+
+    /// Signs the principal for the request with the digest `request_digest`,
+    /// sent to `object` bound under `binding`, see [`Keyring::sign`].
+    pub fn sign(
+        &self,
+        binding: &str,
+        object: &ObjectRef,
+        request_digest: &str,
+    ) -> Result<SignedPrincipal, crate::Error> {
+        self.keyring.sign(
+            binding,
+            object.clone(),
+            request_digest,
+            self.principal.clone(),
+        )
+    }
+
+    /// Signs the principal for `request`, sent to `object` bound under
+    /// `O::BINDING`.
+    pub fn sign_request<O: ObjectApi>(
+        &self,
+        object: &ObjectRef,
+        request: &codec::Request<O>,
+    ) -> Result<SignedPrincipal, crate::Error> {
+        let digest = codec::request_digest(EnvelopeVersion::of::<O>(), request)?;
+        self.sign(O::BINDING, object, &digest)
+    }
+}
+
+/// The current time in seconds since the Unix epoch. Workers have no system
+/// clock, so there it's read from JavaScript's `Date`.
+fn unix_now() -> u64 {
+    #[cfg(all(target_arch = "wasm32", feature = "worker"))]
+    {
+        worker::Date::now().as_millis() / 1000
+    }
+
+    #[cfg(not(all(target_arch = "wasm32", feature = "worker")))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+/// Who may send a command, see [`Policy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// Any caller, with or without a principal.
+    Anyone,
+    /// Any caller with a verified principal.
+    Authenticated,
+    /// Callers whose principal has the given role.
+    Role(String),
+    /// No caller.
+    Nobody,
+}
+
+impl Rule {
+    /// Returns `true` if the rule lets `principal` through.
+    pub fn allows(&self, principal: Option<&Principal>) -> bool {
+        match self {
+            Rule::Anyone => true,
+            Rule::Authenticated => principal.is_some(),
+            Rule::Role(role) => principal.is_some_and(|principal| principal.has_role(role)),
+            Rule::Nobody => false,
+        }
+    }
+}
+
+/// A table of the [`Rule`]s that decide who may send which command, keyed by
+/// the command's name, i.e. the variant name of the object's request enum as
+/// it's serialized, see [`crate::codec::command_name`]. Init requests are
+/// named [`INIT_COMMAND`].
+///
+/// Commands without a rule fall back to the policy's default rule. Only
+/// externally tagged enums, serde's default, have command names, so a policy
+/// with command rules rejects every other request, see
+/// [`Policy::check_request`].
+///
+/// ```ignore
+/// fn policy() -> Policy {
+///     Policy::deny_all()
+///         .command("get", Rule::Authenticated)
+///         .command("insert", Rule::Role("writer".into()))
+///         .command(INIT_COMMAND, Rule::Role("admin".into()))
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    default: Rule,
+    commands: BTreeMap<String, Rule>,
+}
+
+impl Policy {
+    /// A policy that lets anyone send commands without a rule.
+    pub fn allow_all() -> Self {
+        Self::with_default(Rule::Anyone)
+    }
+
+    /// A policy that rejects commands without a rule.
+    pub fn deny_all() -> Self {
+        Self::with_default(Rule::Nobody)
+    }
+
+    /// A policy that applies `rule` to commands without a rule.
+    pub fn with_default(rule: Rule) -> Self {
+        Self {
+            default: rule,
+            commands: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the rule for `command`.
+    pub fn command(mut self, command: impl Into<String>, rule: Rule) -> Self {
+        self.commands.insert(command.into(), rule);
+        self
+    }
+
+    /// The rule that applies to `command`.
+    pub fn rule(&self, command: &str) -> &Rule {
+        self.commands.get(command).unwrap_or(&self.default)
+    }
+
+    /// Checks that `principal` may send `request`, failing with
+    /// [`crate::Error::Forbidden`] otherwise.
+    ///
+    /// The request is only named if the policy has command rules. A request
+    /// that has no command name can't be matched against them and is
+    /// rejected, rather than falling back to the default rule.
+    pub fn check_request<R: Serialize + ?Sized>(
+        &self,
+        principal: Option<&Principal>,
+        request: &R,
+    ) -> Result<(), crate::Error> {
+        if self.commands.is_empty() {
+            if self.default.allows(principal) {
+                return Ok(());
+            }
+
+            let command = crate::codec::command_name(request).unwrap_or("request");
+            return Err(self.forbidden(&self.default, command));
+        }
+
+        match crate::codec::command_name(request) {
+            Some(command) => self.check(principal, command),
+            None => Err(crate::Error::Forbidden {
+                command: std::any::type_name::<R>().to_string(),
+                reason: "the policy names commands, but the request isn't an externally \
+                    tagged enum"
+                    .to_string(),
+            }),
+        }
+    }
+
+    /// Checks that `principal` may send `command`, failing with
+    /// [`crate::Error::Forbidden`] otherwise.
+    pub fn check(&self, principal: Option<&Principal>, command: &str) -> Result<(), crate::Error> {
+        let rule = self.rule(command);
+        if rule.allows(principal) {
+            return Ok(());
+        }
+
+        Err(self.forbidden(rule, command))
+    }
+
+    fn forbidden(&self, rule: &Rule, command: &str) -> crate::Error {
+        let reason = match rule {
+            Rule::Authenticated => "requires a principal".to_string(),
+            Rule::Role(role) => format!("requires the role `{role}`"),
+            Rule::Anyone | Rule::Nobody => "not allowed".to_string(),
+        };
+
+        crate::Error::Forbidden {
+            command: command.to_string(),
+            reason,
+        }
+    }
+}
+
+/// The outcome of an authorization check, passed to `DoProxy::audit`.
+/// Serializes to JSON for structured logs.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct AuditRecord<'a> {
+    /// The verified principal, if the request carried one.
+    pub principal: Option<&'a Principal>,
+    /// The command that was checked, see [`Policy`]. `None` if the request
+    /// has no command name, see [`crate::codec::command_name`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<&'a str>,
+    pub allowed: bool,
+    /// Why the command was rejected.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<&'a crate::Error>,
+}
+
+impl<'a> AuditRecord<'a> {
+    pub fn new(
+        principal: Option<&'a Principal>,
+        command: Option<&'a str>,
+        outcome: &'a Result<(), crate::Error>,
+    ) -> Self {
+        Self {
+            principal,
+            command,
+            allowed: outcome.is_ok(),
+            error: outcome.as_ref().err(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    enum Command {
+        Get { key: String },
+        Clear,
+    }
+
+    #[derive(Serialize)]
+    #[serde(tag = "type")]
+    enum Tagged {
+        Get { key: String },
+        Clear,
+    }
+
+    fn get() -> Command {
+        Command::Get {
+            key: "a".to_string(),
+        }
+    }
+
+    fn writer() -> Principal {
+        Principal::new("fisher").with_role("writer")
+    }
+
+    #[test]
+    fn applies_command_rules() {
+        let policy = Policy::deny_all()
+            .command("Get", Rule::Authenticated)
+            .command("Clear", Rule::Role("writer".into()));
+
+        assert!(policy.check_request(Some(&writer()), &get()).is_ok());
+        assert!(policy
+            .check_request(Some(&writer()), &Command::Clear)
+            .is_ok());
+        assert!(policy.check_request(None, &get()).is_err());
+
+        let reader = Principal::new("fisher");
+        let error = policy
+            .check_request(Some(&reader), &Command::Clear)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::Error::Forbidden { ref command, .. } if command == "Clear"
+        ));
+    }
+
+    #[test]
+    fn falls_back_to_the_default_rule() {
+        let policy = Policy::allow_all().command("Clear", Rule::Nobody);
+
+        assert!(policy.check_request(None, &get()).is_ok());
+        assert!(policy.check_request(None, &Command::Clear).is_err());
+        assert!(Policy::deny_all().check_request(None, &get()).is_err());
+    }
+
+    #[test]
+    fn policies_without_command_rules_accept_any_request_shape() {
+        let request = Tagged::Get {
+            key: "a".to_string(),
+        };
+
+        assert!(Policy::allow_all().check_request(None, &request).is_ok());
+        assert!(Policy::allow_all()
+            .check_request(None, &Tagged::Clear)
+            .is_ok());
+        assert!(Policy::with_default(Rule::Authenticated)
+            .check_request(Some(&writer()), &request)
+            .is_ok());
+        assert!(Policy::deny_all().check_request(None, &request).is_err());
+    }
+
+    #[test]
+    fn command_rules_reject_unnamed_requests() {
+        let policy = Policy::allow_all().command("Clear", Rule::Nobody);
+
+        // An internally tagged `Clear` must not slip through as `type`.
+        assert!(policy.check_request(None, &Tagged::Clear).is_err());
+        assert!(policy
+            .check_request(
+                None,
+                &Tagged::Get {
+                    key: "a".to_string()
+                }
+            )
+            .is_err());
+    }
+
+    fn keyring() -> Keyring {
+        Keyring::new()
+            .with_signing_key("new", "new-secret")
+            .with_key("old", "old-secret")
+            .with_ttl(Duration::from_secs(60))
+    }
+
+    fn fisher() -> ObjectRef {
+        ObjectRef::Name("fisher".to_string())
+    }
+
+    fn sign_at(now: u64) -> SignedPrincipal {
+        keyring()
+            .sign_at("PERSON", fisher(), "digest", writer(), now)
+            .unwrap()
+    }
+
+    #[test]
+    fn verifies_signed_principals() {
+        let signed = sign_at(1_000);
+
+        assert_eq!(signed.key_id, "new");
+        assert_eq!(signed.object, fisher());
+        assert_eq!(signed.expires_at, 1_060);
+        assert_eq!(
+            keyring()
+                .verify_at("PERSON", "digest", &signed, 1_059)
+                .unwrap(),
+            writer()
+        );
+    }
+
+    #[test]
+    fn rejects_expired_principals() {
+        let signed = sign_at(1_000);
+        let error = keyring()
+            .verify_at("PERSON", "digest", &signed, 1_060)
+            .unwrap_err();
+
+        assert!(matches!(
+            error,
+            crate::Error::Unauthenticated { ref reason } if reason.contains("expired")
+        ));
+    }
+
+    #[test]
+    fn rejects_tampered_principals() {
+        let signed = sign_at(1_000);
+
+        let extended = SignedPrincipal {
+            expires_at: signed.expires_at + 3_600,
+            ..signed.clone()
+        };
+        assert!(keyring()
+            .verify_at("PERSON", "digest", &extended, 1_100)
+            .is_err());
+
+        let escalated = SignedPrincipal {
+            principal: writer().with_role("admin"),
+            ..signed.clone()
+        };
+        assert!(keyring()
+            .verify_at("PERSON", "digest", &escalated, 1_000)
+            .is_err());
+
+        assert!(keyring()
+            .verify_at("INSERTER", "digest", &signed, 1_000)
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_principals_replayed_against_other_objects() {
+        let signed = sign_at(1_000);
+
+        for object in [
+            ObjectRef::Name("carp".to_string()),
+            ObjectRef::Id("fisher".to_string()),
+        ] {
+            let replayed = SignedPrincipal {
+                object,
+                ..signed.clone()
+            };
+            assert!(keyring()
+                .verify_at("PERSON", "digest", &replayed, 1_000)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_principals_replayed_with_other_requests() {
+        let signed = sign_at(1_000);
+
+        assert!(keyring()
+            .verify_at("PERSON", "other-digest", &signed, 1_000)
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let signed = Keyring::new()
+            .with_signing_key("other", "other-secret")
+            .sign_at("PERSON", fisher(), "digest", writer(), 1_000)
+            .unwrap();
+
+        assert!(keyring()
+            .verify_at("PERSON", "digest", &signed, 1_000)
+            .is_err());
+    }
+
+    #[test]
+    fn parses_keyrings() {
+        let keyring = Keyring::parse("new:new-secret, old:old-secret").unwrap();
+        let signed = keyring
+            .sign_at("PERSON", fisher(), "digest", writer(), 1_000)
+            .unwrap();
+
+        assert_eq!(signed.key_id, "new");
+        assert!(keyring
+            .verify_at("PERSON", "digest", &signed, 1_000)
+            .is_ok());
+
+        for keys in ["", "new", ":new-secret", "new:", "new:new-secret,old:"] {
+            assert!(Keyring::parse(keys).is_err(), "{keys:?}");
+        }
+    }
+
+    #[test]
+    fn signers_sign_fresh_principals() {
+        assert!(Signer::new(writer(), &Keyring::new()).is_err());
+
+        let signer = Signer::new(writer(), &keyring()).unwrap();
+        let signed = signer.sign("PERSON", &fisher(), "digest").unwrap();
+        assert_eq!(
+            keyring().verify("PERSON", "digest", &signed).unwrap(),
+            writer()
+        );
+    }
+
+    #[test]
+    fn audit_records_serialize_without_a_missing_command() {
+        let outcome = Ok(());
+        let record = AuditRecord::new(None, None, &outcome);
+
+        assert_eq!(
+            serde_json::to_value(record).unwrap(),
+            serde_json::json!({ "principal": null, "allowed": true })
+        );
+    }
+}
@@ -0,0 +1,676 @@
+//! Encoding and decoding of the envelopes in [`crate::transport`].
+//!
+//! Callers encode a [`RequestTransport`] and decode a [`ResponseTransport`],
+//! objects do the opposite. Every implementation of either side should go
+//! through these functions so they agree on the wire format.
+//!
+//! Request envelopes carry an [`EnvelopeVersion`] next to their `type`, which
+//! objects check before decoding the rest of the envelope, and optionally a
+//! [`SignedPrincipal`], see [`crate::auth`]. The principal's signature covers
+//! the [`request_digest`] of the rest of the envelope.
+//!
+//! [`encode_request`] and [`decode_response`] follow the object's
+//! [`ObjectApi::ENCODING`], the other functions always use do-proxy's own
+//! envelopes. See [`crate::jsonrpc`] for the JSON-RPC encoding.
+use std::fmt;
+
+use serde::{de::DeserializeOwned, ser, Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    auth::SignedPrincipal,
+    jsonrpc,
+    transport::{EnvelopeVersion, RequestTransport, ResponseTransport},
+    Encoding, ObjectApi,
+};
+
+/// The request envelope of the object `O`.
+pub type Request<O> = RequestTransport<<O as ObjectApi>::Init, <O as ObjectApi>::Request>;
+
+/// The response envelope of the object `O`.
+pub type Response<O> = ResponseTransport<<O as ObjectApi>::Response, <O as ObjectApi>::Error>;
+
+#[derive(Serialize)]
+struct Versioned<'r, T> {
+    #[serde(flatten)]
+    version: EnvelopeVersion,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    principal: Option<&'r SignedPrincipal>,
+    #[serde(flatten)]
+    envelope: &'r T,
+}
+
+#[derive(Deserialize)]
+struct Signed {
+    #[serde(default)]
+    principal: Option<SignedPrincipal>,
+}
+
+/// A request envelope decoded by [`decode_request_with`].
+pub struct DecodedRequest<O: ObjectApi> {
+    /// The versions the envelope was sent with.
+    pub version: EnvelopeVersion,
+    /// The principal the envelope was sent on behalf of, not verified yet.
+    pub principal: Option<SignedPrincipal>,
+    /// The [`request_digest`] of the envelope, if it was sent with a
+    /// principal.
+    pub digest: Option<String>,
+    pub envelope: Request<O>,
+}
+
+/// Encodes a request envelope for `O`, along with `O`'s versions, in `O`'s
+/// [`ObjectApi::ENCODING`].
+pub fn encode_request<O: ObjectApi>(request: &Request<O>) -> Result<String, crate::Error> {
+    encode_signed_request::<O>(request, None)
+}
+
+/// Encodes a request envelope for `O` like [`encode_request`], sent on behalf
+/// of `principal`. JSON-RPC requests can't carry a principal.
+pub fn encode_signed_request<O: ObjectApi>(
+    request: &Request<O>,
+    principal: Option<&SignedPrincipal>,
+) -> Result<String, crate::Error> {
+    match (O::ENCODING, principal) {
+        (Encoding::Envelope, principal) => {
+            encode_signed_envelope(EnvelopeVersion::of::<O>(), principal, request)
+        }
+        (Encoding::JsonRpc, None) => jsonrpc::encode_request::<O>(request),
+        (Encoding::JsonRpc, Some(_)) => Err(crate::Error::encode(&crate::Cause::new(
+            "JSON-RPC requests can't carry a principal",
+        ))),
+    }
+}
+
+/// Encodes a request envelope along with `version`. Use
+/// [`encode_request`] unless the object's types aren't known at compile time.
+pub fn encode_envelope<Init: Serialize, Request: Serialize>(
+    version: EnvelopeVersion,
+    request: &RequestTransport<Init, Request>,
+) -> Result<String, crate::Error> {
+    encode_signed_envelope(version, None, request)
+}
+
+/// Encodes a request envelope along with `version` and `principal`. Use
+/// [`encode_signed_request`] unless the object's types aren't known at compile
+/// time.
+pub fn encode_signed_envelope<Init: Serialize, Request: Serialize>(
+    version: EnvelopeVersion,
+    principal: Option<&SignedPrincipal>,
+    request: &RequestTransport<Init, Request>,
+) -> Result<String, crate::Error> {
+    let versioned = Versioned {
+        version,
+        principal,
+        envelope: request,
+    };
+
+    serde_json::to_string(&versioned).map_err(|err| crate::Error::encode(&err))
+}
+
+/// Decodes a request envelope sent to `O`. Fails with
+/// [`crate::Error::ProtocolMismatch`] if `O` doesn't support the envelope's
+/// versions.
+pub fn decode_request<O: ObjectApi>(body: &str) -> Result<Request<O>, crate::Error> {
+    let (version, envelope) = decode_versioned(body)?;
+    version.check::<O>()?;
+
+    Request::<O>::deserialize(envelope).map_err(|err| crate::Error::decode(&err, body))
+}
+
+/// Decodes a request envelope sent to `O` by a caller on any supported API
+/// version. The request is handed to `upcast` along with the caller's API
+/// version, which converts it to the current `O::Request`, see [`upcast`].
+pub fn decode_request_with<O: ObjectApi>(
+    body: &str,
+    upcast: impl FnOnce(u32, Value) -> Result<O::Request, crate::Error>,
+) -> Result<DecodedRequest<O>, crate::Error> {
+    let (version, envelope) = decode_versioned(body)?;
+    version.check::<O>()?;
+
+    let Signed { principal } =
+        Signed::deserialize(&envelope).map_err(|err| crate::Error::decode(&err, body))?;
+    let digest = match principal {
+        Some(_) => {
+            let mut unsigned = envelope.clone();
+            if let Value::Object(fields) = &mut unsigned {
+                fields.remove("principal");
+            }
+            Some(digest(unsigned)?)
+        }
+        None => None,
+    };
+    let envelope = RequestTransport::<O::Init, Value>::deserialize(envelope)
+        .map_err(|err| crate::Error::decode(&err, body))?
+        .try_map_request(|request| upcast(version.api_version, request))?;
+
+    Ok(DecodedRequest {
+        version,
+        principal,
+        digest,
+        envelope,
+    })
+}
+
+/// Returns the digest of a request envelope sent along with `version`, which
+/// a [`SignedPrincipal`] sent with the envelope is signed for.
+///
+/// The digest is the hex encoded SHA-256 of the envelope's JSON without the
+/// principal, with the fields of every object sorted, so it doesn't depend on
+/// the order fields were encoded in.
+pub fn request_digest<Init: Serialize, Request: Serialize>(
+    version: EnvelopeVersion,
+    request: &RequestTransport<Init, Request>,
+) -> Result<String, crate::Error> {
+    let versioned = Versioned {
+        version,
+        principal: None,
+        envelope: request,
+    };
+
+    digest(serde_json::to_value(&versioned).map_err(|err| crate::Error::encode(&err))?)
+}
+
+fn digest(mut envelope: Value) -> Result<String, crate::Error> {
+    envelope.sort_all_objects();
+    let json = serde_json::to_vec(&envelope).map_err(|err| crate::Error::encode(&err))?;
+
+    Ok(hex::encode(Sha256::digest(json)))
+}
+
+/// Decodes a request of a legacy type `Legacy` and converts it to the current
+/// request type.
+///
+/// ```ignore
+/// match api_version {
+///     1 => codec::upcast::<PersonRequestV1, _>(request),
+///     _ => codec::upcast::<PersonRequest, _>(request),
+/// }
+/// ```
+pub fn upcast<Legacy, Current>(request: Value) -> Result<Current, crate::Error>
+where
+    Legacy: DeserializeOwned,
+    Current: From<Legacy>,
+{
+    // Deserialized from a reference so the excerpt is only built on failure.
+    Legacy::deserialize(&request)
+        .map(Current::from)
+        .map_err(|err| crate::Error::decode(&err, &request.to_string()))
+}
+
+/// Converts a response to a legacy type `Legacy` and encodes it.
+///
+/// ```ignore
+/// match api_version {
+///     1 => codec::downcast::<PersonResponseV1, _>(response),
+///     _ => codec::downcast::<PersonResponse, _>(response),
+/// }
+/// ```
+pub fn downcast<Legacy, Current>(response: Current) -> Result<Value, crate::Error>
+where
+    Legacy: From<Current> + Serialize,
+{
+    serde_json::to_value(Legacy::from(response)).map_err(|err| crate::Error::encode(&err))
+}
+
+/// The name of the command `request` invokes: the variant name of the
+/// object's request enum, as it's serialized. Used for authorization, see
+/// [`crate::auth::Policy`].
+///
+/// Only externally tagged enums, serde's default, are named. Returns `None`
+/// for anything else, including internally and adjacently tagged enums. Only
+/// the variant is looked at, its fields aren't serialized.
+pub fn command_name<R: Serialize + ?Sized>(request: &R) -> Option<&'static str> {
+    request.serialize(VariantName).ok()
+}
+
+/// Splits a serialized request into its command and the command's body.
+/// Requests must be externally tagged enums, serde's default, so unit variants
+/// are a string and other variants an object with a single entry.
+pub(crate) fn split_command(request: Value) -> Result<(String, Option<Value>), crate::Error> {
+    match request {
+        Value::String(command) => Ok((command, None)),
+        Value::Object(map) if map.len() == 1 => match map.into_iter().next() {
+            Some((command, body)) => Ok((command, Some(body))),
+            None => unreachable!("the map has one entry"),
+        },
+        other => Err(crate::Error::encode(&crate::Cause::new(format!(
+            "expected an externally tagged enum, found {other}"
+        )))),
+    }
+}
+
+/// Returns `true` if `body` is shaped like a request envelope. Objects use it
+/// to recognize envelopes sent without the
+/// [`crate::transport::ENVELOPE_HEADER`] by callers that predate it.
+pub fn is_request_envelope(body: &str) -> bool {
+    let Ok(Value::Object(envelope)) = serde_json::from_str::<Value>(body) else {
+        return false;
+    };
+
+    match envelope.get("type").and_then(Value::as_str) {
+        Some("initWithRequest") => {
+            envelope.contains_key("init") && envelope.contains_key("request")
+        }
+        Some("init") => envelope.contains_key("init"),
+        Some("request") => envelope.contains_key("request"),
+        _ => false,
+    }
+}
+
+/// Serializes a value only as far as needed to find its variant name, see
+/// [`command_name`]. Fails for values that aren't externally tagged enums.
+struct VariantName;
+
+/// The error of [`VariantName`].
+#[derive(Debug)]
+struct NotAVariant;
+
+impl fmt::Display for NotAVariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("not an externally tagged enum variant")
+    }
+}
+
+impl std::error::Error for NotAVariant {}
+
+impl ser::Error for NotAVariant {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        NotAVariant
+    }
+}
+
+macro_rules! not_a_variant {
+    ($($method:ident($($arg:ty),*);)*) => {
+        $(
+            fn $method(self, $(_: $arg),*) -> Result<Self::Ok, Self::Error> {
+                Err(NotAVariant)
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for VariantName {
+    type Ok = &'static str;
+    type Error = NotAVariant;
+    type SerializeSeq = ser::Impossible<&'static str, NotAVariant>;
+    type SerializeTuple = ser::Impossible<&'static str, NotAVariant>;
+    type SerializeTupleStruct = ser::Impossible<&'static str, NotAVariant>;
+    type SerializeTupleVariant = Variant;
+    type SerializeMap = ser::Impossible<&'static str, NotAVariant>;
+    type SerializeStruct = ser::Impossible<&'static str, NotAVariant>;
+    type SerializeStructVariant = Variant;
+
+    not_a_variant! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<Self::Ok, NotAVariant> {
+        Err(NotAVariant)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, NotAVariant> {
+        Ok(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, NotAVariant> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok, NotAVariant> {
+        Ok(variant)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, NotAVariant> {
+        Err(NotAVariant)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, NotAVariant> {
+        Err(NotAVariant)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, NotAVariant> {
+        Err(NotAVariant)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, NotAVariant> {
+        Ok(Variant(variant))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, NotAVariant> {
+        Err(NotAVariant)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, NotAVariant> {
+        Err(NotAVariant)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, NotAVariant> {
+        Ok(Variant(variant))
+    }
+}
+
+/// A tuple or struct variant found by [`VariantName`]. Its fields are skipped.
+struct Variant(&'static str);
+
+impl ser::SerializeTupleVariant for Variant {
+    type Ok = &'static str;
+    type Error = NotAVariant;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, _value: &T) -> Result<(), NotAVariant> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, NotAVariant> {
+        Ok(self.0)
+    }
+}
+
+impl ser::SerializeStructVariant for Variant {
+    type Ok = &'static str;
+    type Error = NotAVariant;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        _value: &T,
+    ) -> Result<(), NotAVariant> {
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, NotAVariant> {
+        Ok(self.0)
+    }
+}
+
+/// Decodes the versions of a request envelope, leaving the rest of it
+/// undecoded.
+pub fn decode_versioned(body: &str) -> Result<(EnvelopeVersion, Value), crate::Error> {
+    let envelope: Value =
+        serde_json::from_str(body).map_err(|err| crate::Error::decode(&err, body))?;
+    let version =
+        EnvelopeVersion::deserialize(&envelope).map_err(|err| crate::Error::decode(&err, body))?;
+
+    Ok((version, envelope))
+}
+
+/// Encodes a response envelope sent by `O`.
+pub fn encode_response<O: ObjectApi>(response: &Response<O>) -> Result<String, crate::Error> {
+    serde_json::to_string(response).map_err(|err| crate::Error::encode(&err))
+}
+
+/// Decodes a response envelope sent by `O` in its [`ObjectApi::ENCODING`].
+pub fn decode_response<O: ObjectApi>(body: &str) -> Result<Response<O>, crate::Error> {
+    match O::ENCODING {
+        Encoding::Envelope => decode_envelope(body),
+        Encoding::JsonRpc => jsonrpc::decode_response::<O>(body),
+    }
+}
+
+/// Decodes a response envelope. Use [`decode_response`] unless the object's
+/// types aren't known at compile time.
+pub fn decode_envelope<Response: DeserializeOwned, Error: DeserializeOwned>(
+    body: &str,
+) -> Result<ResponseTransport<Response, Error>, crate::Error> {
+    serde_json::from_str(body).map_err(|err| crate::Error::decode(&err, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::Validate;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Command {
+        Get { key: String },
+        Clear,
+    }
+
+    impl Validate for Command {}
+
+    struct Api;
+
+    impl ObjectApi for Api {
+        const BINDING: &'static str = "TEST_OBJECT";
+
+        type Init = String;
+        type Request = Command;
+        type Response = Option<String>;
+        type Error = crate::Error;
+    }
+
+    fn get(key: &str) -> Command {
+        Command::Get {
+            key: key.to_string(),
+        }
+    }
+
+    #[derive(Deserialize)]
+    enum LegacyCommand {
+        Fetch(String),
+    }
+
+    impl From<LegacyCommand> for Command {
+        fn from(legacy: LegacyCommand) -> Self {
+            match legacy {
+                LegacyCommand::Fetch(key) => get(&key),
+            }
+        }
+    }
+
+    #[test]
+    fn upcasts_legacy_requests() {
+        let request = serde_json::json!({ "Fetch": "a" });
+        assert_eq!(upcast::<LegacyCommand, Command>(request).unwrap(), get("a"));
+
+        let request = serde_json::json!({ "Fetch": 1 });
+        assert!(matches!(
+            upcast::<LegacyCommand, Command>(request),
+            Err(crate::Error::Decode { excerpt, .. }) if excerpt == r#"{"Fetch":1}"#
+        ));
+    }
+
+    fn decode(body: &str) -> DecodedRequest<Api> {
+        decode_request_with::<Api>(body, |_, request| {
+            Command::deserialize(request).map_err(|err| crate::Error::decode(&err, body))
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn signed_requests_carry_their_digest() {
+        let request = Request::<Api>::Request { request: get("a") };
+        let digest = request_digest(EnvelopeVersion::of::<Api>(), &request).unwrap();
+        let principal = crate::auth::Keyring::new()
+            .with_signing_key("k1", "secret")
+            .sign(
+                Api::BINDING,
+                crate::auth::ObjectRef::Name("a".to_string()),
+                &digest,
+                crate::auth::Principal::new("fisher"),
+            )
+            .unwrap();
+
+        let body = encode_signed_request::<Api>(&request, Some(&principal)).unwrap();
+        assert_eq!(decode(&body).digest, Some(digest.clone()));
+
+        // Envelopes without a principal aren't digested.
+        assert_eq!(
+            decode(&encode_request::<Api>(&request).unwrap()).digest,
+            None
+        );
+
+        // The digest doesn't depend on the order of the fields.
+        let reordered: Value = serde_json::from_str(&body).unwrap();
+        let mut fields: Vec<_> = reordered.as_object().unwrap().iter().collect();
+        fields.reverse();
+        let body = format!(
+            "{{{}}}",
+            fields
+                .iter()
+                .map(|(key, value)| format!("{key:?}:{value}"))
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        assert_eq!(decode(&body).digest, Some(digest.clone()));
+
+        let other = Request::<Api>::Request { request: get("b") };
+        assert_ne!(
+            request_digest(EnvelopeVersion::of::<Api>(), &other).unwrap(),
+            digest
+        );
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename_all = "snake_case")]
+    enum External {
+        Clear,
+        Get(String),
+        Pair(String, String),
+        Insert { key: String, value: String },
+    }
+
+    #[derive(Serialize)]
+    #[serde(tag = "type")]
+    enum Internal {
+        Clear,
+        Get { key: String },
+    }
+
+    #[derive(Serialize)]
+    #[serde(tag = "type", content = "body")]
+    enum Adjacent {
+        Get(String),
+    }
+
+    #[derive(Serialize)]
+    struct Single {
+        key: String,
+    }
+
+    #[derive(Serialize)]
+    struct Wrapper(External);
+
+    #[test]
+    fn names_externally_tagged_variants() {
+        let a = || "a".to_string();
+
+        assert_eq!(command_name(&External::Clear), Some("clear"));
+        assert_eq!(command_name(&External::Get(a())), Some("get"));
+        assert_eq!(command_name(&External::Pair(a(), a())), Some("pair"));
+        assert_eq!(
+            command_name(&External::Insert {
+                key: a(),
+                value: a()
+            }),
+            Some("insert")
+        );
+        assert_eq!(command_name(&Wrapper(External::Clear)), Some("clear"));
+    }
+
+    #[test]
+    fn does_not_name_other_shapes() {
+        let a = || "a".to_string();
+
+        assert_eq!(command_name(&Internal::Clear), None);
+        assert_eq!(command_name(&Internal::Get { key: a() }), None);
+        assert_eq!(command_name(&Adjacent::Get(a())), None);
+        assert_eq!(command_name(&Single { key: a() }), None);
+        assert_eq!(command_name("clear"), None);
+        assert_eq!(command_name(&Some(External::Clear)), None);
+    }
+
+    #[test]
+    fn recognizes_encoded_envelopes() {
+        let envelopes: [Request<Api>; 3] = [
+            RequestTransport::Init {
+                init: "a".to_string(),
+            },
+            RequestTransport::Request { request: get("a") },
+            RequestTransport::InitWithRequest {
+                init: "a".to_string(),
+                request: Command::Clear,
+            },
+        ];
+
+        for envelope in &envelopes {
+            let body = encode_request::<Api>(envelope).unwrap();
+            assert!(is_request_envelope(&body), "{body}");
+        }
+    }
+
+    #[test]
+    fn recognizes_unversioned_envelopes() {
+        assert!(is_request_envelope(
+            r#"{"type":"request","request":{"Get":{"key":"a"}}}"#
+        ));
+        assert!(is_request_envelope(r#"{"type":"init","init":"a"}"#));
+    }
+
+    #[test]
+    fn rejects_other_bodies() {
+        for body in [
+            "",
+            "not json",
+            r#"{"Get":{"key":"a"}}"#,
+            r#"{"type":"request"}"#,
+            r#"{"type":"Get","key":"a"}"#,
+            r#"["request"]"#,
+        ] {
+            assert!(!is_request_envelope(body), "{body}");
+        }
+    }
+}
@@ -0,0 +1,804 @@
+//! Wire-compatibility checks between versions of an object's API. Requires
+//! the `schemars` feature.
+//!
+//! [`compare`] diffs the [`ObjectSchemas`] of two builds and classifies every
+//! difference as breaking or compatible. What the object reads, its init data
+//! and requests, must still accept everything old callers send: removing a
+//! variant or field is breaking, adding a variant or optional field isn't.
+//! What the object writes, its responses and errors, must still be readable
+//! by old callers, so the rules are reversed: adding a variant or making a
+//! field optional is breaking, removing a variant isn't. [`assert_compatible`]
+//! does the same against a snapshot stored in the repository, so breaking
+//! changes fail `cargo test`:
+//!
+//! ```ignore
+//! #[test]
+//! fn inserter_api_is_wire_compatible() {
+//!     do_proxy::compat::assert_compatible::<Inserter>("schemas/INSERTER_OBJECT.json");
+//! }
+//! ```
+//!
+//! Run the test with `DO_PROXY_UPDATE_SNAPSHOTS=1` to accept the current API
+//! as the new snapshot.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs,
+    path::Path,
+};
+
+use schemars::{
+    schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec},
+    JsonSchema, Map,
+};
+
+use crate::{schema::object_schemas, schema::ObjectSchemas, ObjectApi};
+
+/// The environment variable that makes [`assert_compatible`] overwrite the
+/// snapshot instead of comparing against it.
+pub const UPDATE_SNAPSHOTS_VAR: &str = "DO_PROXY_UPDATE_SNAPSHOTS";
+
+/// Whether a [`Change`] breaks callers or objects that weren't redeployed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChangeKind {
+    Breaking,
+    Compatible,
+}
+
+/// A difference between two versions of an object's API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// Where the change is, e.g. `request.insert.key`.
+    pub path: String,
+    pub description: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            ChangeKind::Breaking => "breaking",
+            ChangeKind::Compatible => "compatible",
+        };
+        write!(f, "{kind}: {}: {}", self.path, self.description)
+    }
+}
+
+/// All differences between two versions of an object's API.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompatReport {
+    pub changes: Vec<Change>,
+}
+
+impl CompatReport {
+    /// Returns `true` if none of the changes are breaking.
+    pub fn is_compatible(&self) -> bool {
+        self.breaking().next().is_none()
+    }
+
+    /// The breaking changes.
+    pub fn breaking(&self) -> impl Iterator<Item = &Change> {
+        self.changes
+            .iter()
+            .filter(|change| change.kind == ChangeKind::Breaking)
+    }
+
+    /// The compatible changes.
+    pub fn compatible(&self) -> impl Iterator<Item = &Change> {
+        self.changes
+            .iter()
+            .filter(|change| change.kind == ChangeKind::Compatible)
+    }
+}
+
+impl fmt::Display for CompatReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the API of an object in an `old` and a `new` build.
+pub fn compare(old: &ObjectSchemas, new: &ObjectSchemas) -> CompatReport {
+    let mut diff = Diff::default();
+
+    if old.binding != new.binding {
+        diff.breaking(
+            "binding",
+            format!("changed from `{}` to `{}`", old.binding, new.binding),
+        );
+    }
+
+    if new.min_api_version > old.api_version {
+        diff.breaking(
+            "min_api_version",
+            format!(
+                "{} no longer accepts requests from callers on api version {}",
+                new.min_api_version, old.api_version
+            ),
+        );
+    }
+
+    let roots = [
+        ("init", Direction::Read, &old.init, &new.init),
+        ("request", Direction::Read, &old.request, &new.request),
+        ("response", Direction::Write, &old.response, &new.response),
+        ("error", Direction::Write, &old.error, &new.error),
+        (
+            "request_envelope",
+            Direction::Read,
+            &old.request_envelope,
+            &new.request_envelope,
+        ),
+        (
+            "response_envelope",
+            Direction::Write,
+            &old.response_envelope,
+            &new.response_envelope,
+        ),
+    ];
+    for (path, direction, old, new) in roots {
+        diff.root(path, direction, old, new);
+    }
+
+    CompatReport {
+        changes: diff.changes,
+    }
+}
+
+/// Compares `O`'s API against the snapshot at `path` and panics if there are
+/// breaking changes. Writes the snapshot if it doesn't exist yet, or if
+/// [`UPDATE_SNAPSHOTS_VAR`] is set.
+pub fn assert_compatible<O>(path: impl AsRef<Path>)
+where
+    O: ObjectApi,
+    O::Init: JsonSchema,
+    O::Request: JsonSchema,
+    O::Response: JsonSchema,
+    O::Error: JsonSchema,
+{
+    let path = path.as_ref();
+    let current = object_schemas::<O>();
+
+    if !path.exists() || std::env::var_os(UPDATE_SNAPSHOTS_VAR).is_some() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).expect("failed to create the snapshot directory");
+        }
+        let json = serde_json::to_string_pretty(&current).expect("failed to encode the snapshot");
+        fs::write(path, json + "\n").expect("failed to write the snapshot");
+        return;
+    }
+
+    let snapshot = fs::read_to_string(path).expect("failed to read the snapshot");
+    let snapshot: ObjectSchemas =
+        serde_json::from_str(&snapshot).expect("failed to decode the snapshot");
+
+    let report = compare(&snapshot, &current);
+    assert!(
+        report.is_compatible(),
+        "the API of `{}` is not wire-compatible with {}:\n{report}\
+         Run with {UPDATE_SNAPSHOTS_VAR}=1 to accept it.",
+        O::BINDING,
+        path.display(),
+    );
+}
+
+/// Whether the object reads or writes the values described by a schema.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Direction {
+    /// Sent by callers: the new schema must accept every value the old one
+    /// did.
+    #[default]
+    Read,
+    /// Sent to callers: the old schema must accept every value the new one
+    /// allows.
+    Write,
+}
+
+#[derive(Default)]
+struct Diff<'s> {
+    changes: Vec<Change>,
+    direction: Direction,
+    old_definitions: Option<&'s Map<String, Schema>>,
+    new_definitions: Option<&'s Map<String, Schema>>,
+    /// Pairs of definitions that are being or have been compared, so
+    /// recursive types are only compared once.
+    visited: BTreeSet<(String, String)>,
+}
+
+impl<'s> Diff<'s> {
+    fn breaking(&mut self, path: &str, description: impl Into<String>) {
+        self.push(ChangeKind::Breaking, path, description);
+    }
+
+    fn compatible(&mut self, path: &str, description: impl Into<String>) {
+        self.push(ChangeKind::Compatible, path, description);
+    }
+
+    /// The new schema allows values the old one didn't, which breaks old
+    /// callers reading them.
+    fn widened(&mut self, path: &str, description: impl Into<String>) {
+        match self.direction {
+            Direction::Read => self.compatible(path, description),
+            Direction::Write => self.breaking(path, description),
+        }
+    }
+
+    /// The new schema no longer allows values the old one did, which breaks
+    /// old callers sending them.
+    fn narrowed(&mut self, path: &str, description: impl Into<String>) {
+        match self.direction {
+            Direction::Read => self.breaking(path, description),
+            Direction::Write => self.compatible(path, description),
+        }
+    }
+
+    fn push(&mut self, kind: ChangeKind, path: &str, description: impl Into<String>) {
+        self.changes.push(Change {
+            kind,
+            path: path.to_string(),
+            description: description.into(),
+        });
+    }
+
+    fn root(&mut self, path: &str, direction: Direction, old: &'s RootSchema, new: &'s RootSchema) {
+        self.direction = direction;
+        self.old_definitions = Some(&old.definitions);
+        self.new_definitions = Some(&new.definitions);
+        self.visited.clear();
+        self.object(path, &old.schema, &new.schema);
+    }
+
+    fn schema(&mut self, path: &str, old: &'s Schema, new: &'s Schema) {
+        match (old, new) {
+            (Schema::Object(old), Schema::Object(new)) => self.object(path, old, new),
+            (Schema::Bool(old), Schema::Bool(new)) if old == new => {}
+            (_, Schema::Bool(true)) | (Schema::Bool(false), _) => {
+                self.widened(path, "allows more values")
+            }
+            (Schema::Bool(true), _) | (_, Schema::Bool(false)) => {
+                self.narrowed(path, "allows fewer values")
+            }
+        }
+    }
+
+    fn object(&mut self, path: &str, old: &'s SchemaObject, new: &'s SchemaObject) {
+        if let (Some(old_ref), Some(new_ref)) = (&old.reference, &new.reference) {
+            if !self.visited.insert((old_ref.clone(), new_ref.clone())) {
+                return;
+            }
+        }
+
+        let (Some(old), Some(new)) = (self.resolve(old, true), self.resolve(new, false)) else {
+            return self.breaking(path, "refers to an unknown definition");
+        };
+
+        self.instance_types(path, old, new);
+        self.variants(path, old, new);
+        self.properties(path, old, new);
+
+        let old_items = old.array.as_ref().and_then(|array| array.items.as_ref());
+        let new_items = new.array.as_ref().and_then(|array| array.items.as_ref());
+        match (old_items, new_items) {
+            (Some(SingleOrVec::Single(old)), Some(SingleOrVec::Single(new))) => {
+                self.schema(&format!("{path}[]"), old, new)
+            }
+            (Some(SingleOrVec::Vec(old)), Some(SingleOrVec::Vec(new))) => {
+                if old.len() != new.len() {
+                    self.breaking(
+                        path,
+                        format!("tuple length changed from {} to {}", old.len(), new.len()),
+                    );
+                }
+                for (index, (old, new)) in old.iter().zip(new).enumerate() {
+                    self.schema(&format!("{path}.{index}"), old, new);
+                }
+            }
+            (None, None) => {}
+            _ => self.breaking(path, "array items changed shape"),
+        }
+    }
+
+    fn resolve(&self, schema: &'s SchemaObject, old: bool) -> Option<&'s SchemaObject> {
+        let Some(reference) = &schema.reference else {
+            return Some(schema);
+        };
+
+        let definitions = if old {
+            self.old_definitions
+        } else {
+            self.new_definitions
+        };
+        match definitions?.get(reference.trim_start_matches("#/definitions/"))? {
+            Schema::Object(schema) => self.resolve(schema, old),
+            Schema::Bool(_) => None,
+        }
+    }
+
+    fn instance_types(&mut self, path: &str, old: &SchemaObject, new: &SchemaObject) {
+        let (old, new) = (instance_types(old), instance_types(new));
+        if old.is_empty() || new.is_empty() {
+            return;
+        }
+
+        for removed in old.difference(&new) {
+            self.narrowed(path, format!("no longer allows {removed}"));
+        }
+        for added in new.difference(&old) {
+            self.widened(path, format!("now also allows {added}"));
+        }
+    }
+
+    fn variants(&mut self, path: &str, old: &'s SchemaObject, new: &'s SchemaObject) {
+        let (old, new) = (self.variants_of(old, true), self.variants_of(new, false));
+
+        for (key, old_variant) in &old {
+            match new.get(key) {
+                Some(new_variant) => {
+                    if let (Some(old_variant), Some(new_variant)) = (old_variant, new_variant) {
+                        self.schema(&format!("{path}.{key}"), old_variant, new_variant);
+                    }
+                }
+                None => self.narrowed(path, format!("variant `{key}` was removed")),
+            }
+        }
+
+        for key in new.keys().filter(|key| !old.contains_key(*key)) {
+            self.widened(path, format!("variant `{key}` was added"));
+        }
+    }
+
+    /// The variants of an enum, keyed by how they are told apart on the wire:
+    /// their tag for internally tagged enums, their only field for externally
+    /// tagged ones and their value for unit variants.
+    fn variants_of(
+        &self,
+        schema: &'s SchemaObject,
+        old: bool,
+    ) -> BTreeMap<String, Option<&'s Schema>> {
+        let mut variants = BTreeMap::new();
+
+        for value in schema.enum_values.iter().flatten() {
+            variants.insert(value.to_string(), None);
+        }
+
+        let Some(subschemas) = &schema.subschemas else {
+            return variants;
+        };
+        let alternatives = [&subschemas.one_of, &subschemas.any_of]
+            .into_iter()
+            .flatten()
+            .flatten();
+
+        for (index, alternative) in alternatives.enumerate() {
+            let Schema::Object(object) = alternative else {
+                continue;
+            };
+            let resolved = self.resolve(object, old);
+
+            // Unit variants of an enum that also has other variants share an
+            // alternative.
+            if let Some(values) = resolved.and_then(|object| object.enum_values.as_ref()) {
+                for value in values {
+                    variants.insert(value.to_string(), None);
+                }
+                continue;
+            }
+
+            let key = resolved
+                .and_then(variant_key)
+                .unwrap_or_else(|| format!("#{index}"));
+            variants.insert(key, Some(alternative));
+        }
+
+        variants
+    }
+
+    fn properties(&mut self, path: &str, old: &'s SchemaObject, new: &'s SchemaObject) {
+        let (Some(old), Some(new)) = (&old.object, &new.object) else {
+            return;
+        };
+
+        for (name, old_property) in &old.properties {
+            let property_path = format!("{path}.{name}");
+            match new.properties.get(name) {
+                Some(new_property) => {
+                    if !old.required.contains(name) && new.required.contains(name) {
+                        self.narrowed(&property_path, "became required");
+                    } else if old.required.contains(name) && !new.required.contains(name) {
+                        self.widened(&property_path, "became optional");
+                    }
+                    self.schema(&property_path, old_property, new_property);
+                }
+                // Callers that still send the field may mean a renamed one.
+                None if self.direction == Direction::Read => {
+                    self.breaking(path, format!("field `{name}` was removed or renamed"))
+                }
+                None if old.required.contains(name) => {
+                    self.breaking(path, format!("required field `{name}` was removed"))
+                }
+                None => self.compatible(path, format!("optional field `{name}` was removed")),
+            }
+        }
+
+        // Readers skip unknown fields unless they deny them.
+        let denies_unknown = matches!(
+            old.additional_properties.as_deref(),
+            Some(Schema::Bool(false))
+        );
+        for name in new.properties.keys() {
+            if old.properties.contains_key(name) {
+                continue;
+            }
+
+            let required = new.required.contains(name);
+            let description = if required {
+                format!("required field `{name}` was added")
+            } else {
+                format!("optional field `{name}` was added")
+            };
+            match self.direction {
+                Direction::Read if required => self.breaking(path, description),
+                Direction::Write if denies_unknown => self.breaking(path, description),
+                _ => self.compatible(path, description),
+            }
+        }
+    }
+}
+
+fn instance_types(schema: &SchemaObject) -> BTreeSet<String> {
+    let types = match &schema.instance_type {
+        Some(SingleOrVec::Single(instance_type)) => vec![**instance_type],
+        Some(SingleOrVec::Vec(instance_types)) => instance_types.clone(),
+        None => Vec::new(),
+    };
+
+    types
+        .into_iter()
+        .map(|instance_type| {
+            match instance_type {
+                // Integers are numbers on the wire.
+                InstanceType::Integer => InstanceType::Number,
+                instance_type => instance_type,
+            }
+        })
+        .map(|instance_type| format!("{instance_type:?}").to_lowercase())
+        .collect()
+}
+
+fn variant_key(schema: &SchemaObject) -> Option<String> {
+    let Some(object) = &schema.object else {
+        let types = instance_types(schema);
+        return (types.len() == 1).then(|| types.into_iter().next().unwrap());
+    };
+
+    // An internally tagged variant has a property with a single allowed value.
+    for (name, property) in &object.properties {
+        if let Schema::Object(property) = property {
+            if let Some([value]) = property.enum_values.as_deref() {
+                return Some(format!("{name}={value}"));
+            }
+            if let Some(value) = &property.const_value {
+                return Some(format!("{name}={value}"));
+            }
+        }
+    }
+
+    // An externally tagged variant has exactly one required property.
+    match (object.properties.len(), object.required.len()) {
+        (1, 1) => object.required.iter().next().cloned(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use schemars::JsonSchema;
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+    use super::*;
+
+    struct Api<Request, Response>(PhantomData<(Request, Response)>);
+
+    /// Lets any type be used as a request, with the schema of `T`.
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(transparent)]
+    struct Payload<T>(T);
+
+    impl<T> crate::Validate for Payload<T> {}
+
+    impl<Request, Response> ObjectApi for Api<Request, Response>
+    where
+        Request: Serialize + DeserializeOwned + JsonSchema + 'static,
+        Response: Serialize + DeserializeOwned + 'static,
+    {
+        const BINDING: &'static str = "TEST_OBJECT";
+
+        type Init = ();
+        type Request = Payload<Request>;
+        type Response = Response;
+        type Error = crate::Error;
+    }
+
+    /// Compares an API with `Old` requests and responses against one with
+    /// `New` ones.
+    fn compare_apis<Old, New>() -> CompatReport
+    where
+        Old: Serialize + DeserializeOwned + JsonSchema + 'static,
+        New: Serialize + DeserializeOwned + JsonSchema + 'static,
+    {
+        compare(
+            &object_schemas::<Api<Old, Old>>(),
+            &object_schemas::<Api<New, New>>(),
+        )
+    }
+
+    /// The kind of the change described by `description` at `path`.
+    fn kind_of(report: &CompatReport, path: &str, description: &str) -> ChangeKind {
+        report
+            .changes
+            .iter()
+            .find(|change| change.path == path && change.description.contains(description))
+            .unwrap_or_else(|| panic!("no change `{description}` at `{path}` in:\n{report}"))
+            .kind
+    }
+
+    mod v1 {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        pub enum Command {
+            Get { key: String },
+            Clear,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        pub struct Entry {
+            pub key: String,
+            pub value: String,
+            pub note: Option<String>,
+            pub size: u32,
+        }
+    }
+
+    mod added_variant {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        pub enum Command {
+            Get { key: String },
+            Clear,
+            Count,
+        }
+    }
+
+    mod removed_variant {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        pub enum Command {
+            Get { key: String },
+        }
+    }
+
+    mod changed_fields {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        pub struct Entry {
+            pub key: String,
+            pub value: Option<String>,
+            pub note: String,
+            pub size: u32,
+            pub created: String,
+            pub tag: Option<String>,
+        }
+    }
+
+    mod removed_fields {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        pub struct Entry {
+            pub key: String,
+            pub size: u32,
+        }
+    }
+
+    mod widened_type {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        pub struct Entry {
+            pub key: String,
+            pub value: String,
+            pub note: Option<String>,
+            pub size: Option<u32>,
+        }
+    }
+
+    mod strict {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(deny_unknown_fields)]
+        pub struct Entry {
+            pub key: String,
+        }
+    }
+
+    mod strict_added_field {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(deny_unknown_fields)]
+        pub struct Entry {
+            pub key: String,
+            pub tag: Option<String>,
+        }
+    }
+
+    #[test]
+    fn identical_apis_have_no_changes() {
+        assert_eq!(
+            compare_apis::<v1::Entry, v1::Entry>(),
+            CompatReport::default()
+        );
+    }
+
+    #[test]
+    fn added_variants_break_readers_of_responses_only() {
+        let report = compare_apis::<v1::Command, added_variant::Command>();
+
+        let added = "variant `\"Count\"` was added";
+        assert_eq!(kind_of(&report, "request", added), ChangeKind::Compatible);
+        assert_eq!(kind_of(&report, "response", added), ChangeKind::Breaking);
+        assert!(!report.is_compatible());
+    }
+
+    #[test]
+    fn removed_variants_break_senders_of_requests_only() {
+        let report = compare_apis::<v1::Command, removed_variant::Command>();
+
+        let removed = "variant `\"Clear\"` was removed";
+        assert_eq!(kind_of(&report, "request", removed), ChangeKind::Breaking);
+        assert_eq!(
+            kind_of(&report, "response", removed),
+            ChangeKind::Compatible
+        );
+    }
+
+    #[test]
+    fn requiredness_changes_are_reversed_for_responses() {
+        let report = compare_apis::<v1::Entry, changed_fields::Entry>();
+
+        assert_eq!(
+            kind_of(&report, "request.value", "became optional"),
+            ChangeKind::Compatible
+        );
+        assert_eq!(
+            kind_of(&report, "response.value", "became optional"),
+            ChangeKind::Breaking
+        );
+        assert_eq!(
+            kind_of(&report, "request.note", "became required"),
+            ChangeKind::Breaking
+        );
+        assert_eq!(
+            kind_of(&report, "response.note", "became required"),
+            ChangeKind::Compatible
+        );
+    }
+
+    #[test]
+    fn added_fields_only_break_requests_if_required() {
+        let report = compare_apis::<v1::Entry, changed_fields::Entry>();
+
+        let required = "required field `created` was added";
+        let optional = "optional field `tag` was added";
+        assert_eq!(kind_of(&report, "request", required), ChangeKind::Breaking);
+        assert_eq!(
+            kind_of(&report, "request", optional),
+            ChangeKind::Compatible
+        );
+        assert_eq!(
+            kind_of(&report, "response", required),
+            ChangeKind::Compatible
+        );
+        assert_eq!(
+            kind_of(&report, "response", optional),
+            ChangeKind::Compatible
+        );
+    }
+
+    #[test]
+    fn added_fields_break_readers_that_deny_unknown_fields() {
+        let report = compare_apis::<strict::Entry, strict_added_field::Entry>();
+
+        let added = "optional field `tag` was added";
+        assert_eq!(kind_of(&report, "request", added), ChangeKind::Compatible);
+        assert_eq!(kind_of(&report, "response", added), ChangeKind::Breaking);
+    }
+
+    #[test]
+    fn removed_fields_only_break_responses_if_required() {
+        let report = compare_apis::<v1::Entry, removed_fields::Entry>();
+
+        assert_eq!(
+            kind_of(&report, "request", "field `value` was removed or renamed"),
+            ChangeKind::Breaking
+        );
+        assert_eq!(
+            kind_of(&report, "request", "field `note` was removed or renamed"),
+            ChangeKind::Breaking
+        );
+        assert_eq!(
+            kind_of(&report, "response", "required field `value` was removed"),
+            ChangeKind::Breaking
+        );
+        assert_eq!(
+            kind_of(&report, "response", "optional field `note` was removed"),
+            ChangeKind::Compatible
+        );
+    }
+
+    #[test]
+    fn widened_types_break_readers_of_responses_only() {
+        let report = compare_apis::<v1::Entry, widened_type::Entry>();
+
+        let widened = "now also allows null";
+        assert_eq!(
+            kind_of(&report, "request.size", widened),
+            ChangeKind::Compatible
+        );
+        assert_eq!(
+            kind_of(&report, "response.size", widened),
+            ChangeKind::Breaking
+        );
+    }
+
+    #[test]
+    fn narrowed_types_break_senders_of_requests_only() {
+        let report = compare_apis::<widened_type::Entry, v1::Entry>();
+
+        let narrowed = "no longer allows null";
+        assert_eq!(
+            kind_of(&report, "request.size", narrowed),
+            ChangeKind::Breaking
+        );
+        assert_eq!(
+            kind_of(&report, "response.size", narrowed),
+            ChangeKind::Compatible
+        );
+    }
+
+    #[test]
+    fn response_envelopes_are_compared_as_written() {
+        let report = compare_apis::<v1::Command, added_variant::Command>();
+
+        assert!(report
+            .breaking()
+            .any(|change| change.path.starts_with("response_envelope")));
+        assert!(report
+            .compatible()
+            .any(|change| change.path.starts_with("request_envelope")));
+    }
+
+    #[test]
+    fn binding_and_min_api_version_changes_are_breaking() {
+        let old = object_schemas::<Api<v1::Entry, v1::Entry>>();
+        let mut new = old.clone();
+        new.binding = "OTHER_OBJECT".to_string();
+        new.min_api_version = 1;
+
+        let report = compare(&old, &new);
+        assert_eq!(kind_of(&report, "binding", "changed"), ChangeKind::Breaking);
+        assert_eq!(
+            kind_of(&report, "min_api_version", "no longer accepts"),
+            ChangeKind::Breaking
+        );
+    }
+}
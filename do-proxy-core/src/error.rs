@@ -0,0 +1,284 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::FieldError;
+
+/// The longest excerpt of an undecodable payload that is kept in
+/// [`Error::Decode`].
+const EXCERPT_LEN: usize = 128;
+
+/// The errors returned by do-proxy.
+///
+/// Every variant has a stable [`code`](Error::code), which is also how the
+/// error is tagged when it's serialized, so errors sent by an object can be
+/// matched on by callers in other workers or native services.
+///
+/// Errors used to be serialized with serde's default external tagging, e.g.
+/// `{"Worker": "..."}`. Callers built before the change can't decode errors
+/// of objects built after it, including objects that use this type as their
+/// `ObjectApi::Error`, so deploy objects and their callers together when
+/// upgrading across it.
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum Error {
+    /// An envelope or payload couldn't be serialized.
+    #[error("failed to encode: {cause}")]
+    Encode {
+        #[source]
+        cause: Cause,
+    },
+    /// An envelope or payload couldn't be deserialized. `excerpt` holds the
+    /// start of the payload.
+    #[error("failed to decode `{excerpt}`: {cause}")]
+    Decode {
+        #[source]
+        cause: Cause,
+        excerpt: String,
+    },
+    /// The request couldn't be delivered to the object, or the object's
+    /// response couldn't be read.
+    #[error("failed to reach the object: {cause}")]
+    StubFetch {
+        #[source]
+        cause: Cause,
+    },
+    /// The object, or a gateway in front of it, responded with a status that
+    /// isn't `2xx`.
+    #[error("unexpected status {status}: {excerpt}")]
+    UnexpectedStatus { status: u16, excerpt: String },
+    /// An HTTP client failed to talk to a gateway.
+    #[error("http: {cause}")]
+    Http {
+        #[source]
+        cause: Cause,
+    },
+    /// Any other error raised by the Workers runtime.
+    #[error("worker: {cause}")]
+    Worker {
+        #[source]
+        cause: Cause,
+    },
+    /// The object couldn't be loaded from storage because it was never
+    /// initialized. do-proxy doesn't raise it itself, it's meant for objects
+    /// whose error type is [`Error`] to return from `load_from_storage`.
+    #[error("object not initialized: {cause}")]
+    ObjectNotInitialized {
+        #[source]
+        cause: Cause,
+    },
+    #[error("expected object response")]
+    ExpectedObjectResponse,
+    #[error("expected object to be initialized")]
+    ExpectedObjectInitialized,
+    #[error("deadline exceeded")]
+    DeadlineExceeded,
+    /// The caller and the object don't speak the same protocol.
+    #[error("protocol mismatch: expected {expected}, found {found}")]
+    ProtocolMismatch { expected: String, found: String },
+    /// The object requires a signed principal and the request didn't carry a
+    /// valid one, see [`crate::auth`].
+    #[error("unauthenticated: {reason}")]
+    Unauthenticated { reason: String },
+    /// The caller isn't allowed to send `command`, see
+    /// [`crate::auth::Policy`].
+    #[error("forbidden to send `{command}`: {reason}")]
+    Forbidden { command: String, reason: String },
+    /// A request or init payload failed validation, see [`crate::Validate`].
+    #[error("validation failed: {}", describe_fields(fields))]
+    Validation { fields: Vec<FieldError> },
+    /// The request envelope is larger than the object accepts.
+    #[error("payload of {size} bytes exceeds the limit of {limit} bytes")]
+    PayloadTooLarge { size: usize, limit: usize },
+    /// The object's request type has no command named `command`, see
+    /// [`crate::jsonrpc`].
+    #[error("unknown command `{command}`")]
+    UnknownCommand { command: String },
+}
+
+impl Error {
+    /// Creates an [`Error::Encode`].
+    pub fn encode(err: &(dyn std::error::Error + 'static)) -> Self {
+        Error::Encode {
+            cause: Cause::from_error(err),
+        }
+    }
+
+    /// Creates an [`Error::Decode`] for `payload`.
+    pub fn decode(err: &(dyn std::error::Error + 'static), payload: &str) -> Self {
+        Error::Decode {
+            cause: Cause::from_error(err),
+            excerpt: excerpt(payload),
+        }
+    }
+
+    /// Creates an [`Error::StubFetch`].
+    pub fn stub_fetch(err: &(dyn std::error::Error + 'static)) -> Self {
+        Error::StubFetch {
+            cause: Cause::from_error(err),
+        }
+    }
+
+    /// Creates an [`Error::UnexpectedStatus`] for a response with `body`.
+    pub fn unexpected_status(status: u16, body: &str) -> Self {
+        Error::UnexpectedStatus {
+            status,
+            excerpt: excerpt(body),
+        }
+    }
+
+    /// Creates an [`Error::Http`].
+    pub fn http(err: &(dyn std::error::Error + 'static)) -> Self {
+        Error::Http {
+            cause: Cause::from_error(err),
+        }
+    }
+
+    /// Creates an [`Error::Worker`] with a message.
+    pub fn worker(message: impl Into<String>) -> Self {
+        Error::Worker {
+            cause: Cause::new(message),
+        }
+    }
+
+    /// Creates an [`Error::ObjectNotInitialized`].
+    pub fn object_not_initialized(err: &(dyn std::error::Error + 'static)) -> Self {
+        Error::ObjectNotInitialized {
+            cause: Cause::from_error(err),
+        }
+    }
+
+    /// A stable, machine-readable code for the error, e.g. `deadline_exceeded`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Encode { .. } => "encode",
+            Error::Decode { .. } => "decode",
+            Error::StubFetch { .. } => "stub_fetch",
+            Error::UnexpectedStatus { .. } => "unexpected_status",
+            Error::Http { .. } => "http",
+            Error::Worker { .. } => "worker",
+            Error::ObjectNotInitialized { .. } => "object_not_initialized",
+            Error::ExpectedObjectResponse => "expected_object_response",
+            Error::ExpectedObjectInitialized => "expected_object_initialized",
+            Error::DeadlineExceeded => "deadline_exceeded",
+            Error::ProtocolMismatch { .. } => "protocol_mismatch",
+            Error::Unauthenticated { .. } => "unauthenticated",
+            Error::Forbidden { .. } => "forbidden",
+            Error::Validation { .. } => "validation",
+            Error::PayloadTooLarge { .. } => "payload_too_large",
+            Error::UnknownCommand { .. } => "unknown_command",
+        }
+    }
+
+    /// Returns `true` if sending the same request again may succeed, for
+    /// example after the object couldn't be reached or took too long.
+    ///
+    /// Note that a retried request may be handled twice if the first attempt
+    /// reached the object.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::StubFetch { .. } | Error::Http { .. } | Error::DeadlineExceeded => true,
+            Error::UnexpectedStatus { status, .. } => {
+                matches!(status, 429 | 502 | 503 | 504)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn describe_fields(fields: &[FieldError]) -> String {
+    fields
+        .iter()
+        .map(|error| format!("{}: {}", error.field, error.message))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The cause of an [`Error`]: the message of the underlying error and of
+/// every error in its source chain.
+///
+/// Unlike the underlying error, a cause can be serialized, so the chain
+/// survives being sent from an object to its caller.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Cause {
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Box<Cause>>,
+}
+
+impl Cause {
+    /// Creates a cause without a source.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Captures the message and source chain of `err`.
+    pub fn from_error(err: &(dyn std::error::Error + 'static)) -> Self {
+        Self {
+            message: err.to_string(),
+            source: err
+                .source()
+                .map(|source| Box::new(Cause::from_error(source))),
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Cause {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn std::error::Error + 'static))
+    }
+}
+
+fn excerpt(payload: &str) -> String {
+    match payload.char_indices().nth(EXCERPT_LEN) {
+        Some((end, _)) => format!("{}...", &payload[..end]),
+        None => payload.to_string(),
+    }
+}
+
+#[cfg(feature = "worker")]
+impl From<worker::Error> for Error {
+    fn from(err: worker::Error) -> Self {
+        Error::Worker {
+            cause: Cause::from_error(&err),
+        }
+    }
+}
+
+#[cfg(feature = "worker")]
+impl From<Error> for worker::Error {
+    fn from(err: Error) -> Self {
+        worker::Error::from(err.to_string())
+    }
+}
+
+/// An enum of either a [`crate::Error`] or a user provided error, usually a [`crate::ObjectApi::Error`].
+#[derive(Debug, Error)]
+pub enum CrateOrObjectError<ObjectError> {
+    Crate(#[from] Error),
+    Object(ObjectError),
+}
+
+#[cfg(feature = "worker")]
+impl<ObjectError: std::error::Error> From<CrateOrObjectError<ObjectError>> for worker::Error {
+    fn from(err: CrateOrObjectError<ObjectError>) -> Self {
+        match err {
+            CrateOrObjectError::Crate(err) => err.into(),
+            CrateOrObjectError::Object(err) => worker::Error::from(err.to_string()),
+        }
+    }
+}
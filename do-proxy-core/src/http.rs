@@ -0,0 +1,324 @@
+use std::marker::PhantomData;
+
+use crate::{
+    auth::{Keyring, ObjectRef, Principal, Signer},
+    codec,
+    transport::{RequestTransport, BINDING_HEADER, NAME_HEADER},
+    CrateOrObjectError, ObjectApi,
+};
+
+/// A proxy for services that run outside of Cloudflare Workers.
+///
+/// [`HttpProxy`] sends the same request envelopes as `do_proxy::Proxy`, but
+/// posts them to a gateway worker over HTTP. The gateway forwards them to the
+/// object, for example with a `do_proxy::ObjectRouter` or
+/// `do_proxy::forward_envelope`. It only uses `reqwest`, so it can be used from
+/// native services and tests; point it at a local server to test against a
+/// stand-in for the gateway.
+///
+/// Requires the `http-client` feature.
+///
+/// # Example
+///
+/// ```ignore
+/// let inserter = HttpProxy::<Inserter>::new("https://gateway.example.com/", "inserter_for_fisher");
+/// let resp = inserter.send(InserterRequest::Get { key: "hello".into() }).await?;
+/// ```
+pub struct HttpProxy<O> {
+    client: reqwest::Client,
+    url: String,
+    binding: String,
+    name: String,
+    signer: Option<Signer>,
+    _phantom: PhantomData<O>,
+}
+
+impl<O: ObjectApi> HttpProxy<O> {
+    /// Creates a proxy to the object with the given name behind the gateway at
+    /// `gateway_url`.
+    pub fn new(gateway_url: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: gateway_url.into(),
+            binding: O::BINDING.to_string(),
+            name: name.into(),
+            signer: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Uses `client` to send requests, for example to share a connection pool
+    /// or to set default headers for authenticating with the gateway.
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Sends requests to the object under `binding` instead of
+    /// [`ObjectApi::BINDING`].
+    pub fn with_binding(mut self, binding: impl Into<String>) -> Self {
+        self.binding = binding.into();
+        self
+    }
+
+    /// Sends every request on behalf of `principal`, signed with `keyring`.
+    /// See `do_proxy::Proxy::with_principal`.
+    pub fn with_principal(
+        mut self,
+        principal: Principal,
+        keyring: &Keyring,
+    ) -> Result<Self, crate::Error> {
+        self.signer = Some(Signer::new(principal, keyring)?);
+        Ok(self)
+    }
+
+    /// Send a request to the durable object.
+    ///
+    /// ```ignore
+    /// let resp = proxy.send(Command::GetBirthday).await?;
+    /// ```
+    pub async fn send(
+        &self,
+        request: O::Request,
+    ) -> Result<O::Response, CrateOrObjectError<O::Error>> {
+        self.post(RequestTransport::Request { request })
+            .await?
+            .into_response()
+    }
+
+    /// Initialize the durable object. See `do_proxy::Proxy::init`.
+    pub async fn init(&self, init: O::Init) -> Result<Result<(), O::Error>, crate::Error> {
+        self.post(RequestTransport::Init { init })
+            .await?
+            .into_initialized()
+    }
+
+    /// Initialize the durable object and have it handle `request` afterwards.
+    pub async fn init_and_send(
+        &self,
+        init: O::Init,
+        request: O::Request,
+    ) -> Result<O::Response, CrateOrObjectError<O::Error>> {
+        self.post(RequestTransport::InitWithRequest { init, request })
+            .await?
+            .into_response()
+    }
+
+    async fn post(&self, req: codec::Request<O>) -> Result<codec::Response<O>, crate::Error> {
+        let principal = self
+            .signer
+            .as_ref()
+            .map(|signer| signer.sign_request::<O>(&ObjectRef::Name(self.name.clone()), &req))
+            .transpose()?;
+        let json = codec::encode_signed_request::<O>(&req, principal.as_ref())?;
+
+        let response = self
+            .client
+            .post(&self.url)
+            .header(BINDING_HEADER, &self.binding)
+            .header(NAME_HEADER, &self.name)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(json)
+            .send()
+            .await
+            .map_err(|err| crate::Error::http(&err))?;
+
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|err| crate::Error::http(&err))?;
+
+        if !status.is_success() {
+            return Err(crate::Error::unexpected_status(status.as_u16(), &body));
+        }
+
+        codec::decode_response::<O>(&body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::auth::SignedPrincipal;
+    use crate::transport::{EnvelopeVersion, ResponseTransport};
+    use crate::Validate;
+
+    struct Counter;
+
+    #[derive(Serialize, Deserialize)]
+    enum CounterRequest {
+        Add(u32),
+    }
+
+    impl Validate for CounterRequest {}
+
+    impl ObjectApi for Counter {
+        const BINDING: &'static str = "COUNTER";
+
+        type Init = u32;
+        type Request = CounterRequest;
+        type Response = u32;
+        type Error = crate::Error;
+    }
+
+    /// A request as the stand-in gateway received it.
+    struct Received {
+        request_line: String,
+        headers: Vec<(String, String)>,
+        body: Value,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(header, _)| header.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    /// Serves a single request with `status` and `body` on a local port, and
+    /// returns the gateway's URL and the request it received.
+    fn stand_in(status: u16, body: impl Into<String>) -> (String, thread::JoinHandle<Received>) {
+        let body = body.into();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let gateway = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            let mut headers = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let Some((name, value)) = line.trim_end().split_once(':') else {
+                    break;
+                };
+                headers.push((name.to_string(), value.trim().to_string()));
+            }
+
+            let length = headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                .map_or(0, |(_, value)| value.parse().unwrap());
+            let mut request_body = vec![0; length];
+            reader.read_exact(&mut request_body).unwrap();
+
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {status} Stand-in\r\ncontent-type: application/json\r\n\
+                content-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+
+            Received {
+                request_line: request_line.trim_end().to_string(),
+                headers,
+                body: serde_json::from_slice(&request_body).unwrap(),
+            }
+        });
+
+        (url, gateway)
+    }
+
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn posts_envelopes_to_the_gateway() {
+        let (url, gateway) = stand_in(200, r#"{"type":"response","response":3}"#);
+        let proxy = HttpProxy::<Counter>::new(url, "visits");
+
+        let response = block_on(proxy.send(CounterRequest::Add(2))).unwrap();
+        let received = gateway.join().unwrap();
+
+        assert_eq!(response, 3);
+        assert_eq!(received.request_line, "POST / HTTP/1.1");
+        assert_eq!(received.header(BINDING_HEADER), Some("COUNTER"));
+        assert_eq!(received.header(NAME_HEADER), Some("visits"));
+        assert_eq!(received.body["type"], "request");
+        assert_eq!(received.body["request"], json!({ "Add": 2 }));
+    }
+
+    #[test]
+    fn sends_signed_principals() {
+        let keyring = Keyring::new().with_signing_key("k1", "secret");
+        let (url, gateway) = stand_in(200, r#"{"type":"initialized"}"#);
+        let proxy = HttpProxy::<Counter>::new(url, "visits")
+            .with_binding("COUNTER_V2")
+            .with_principal(Principal::new("fisher"), &keyring)
+            .unwrap();
+
+        block_on(proxy.init(1)).unwrap().unwrap();
+        let received = gateway.join().unwrap();
+
+        assert_eq!(received.header(BINDING_HEADER), Some("COUNTER_V2"));
+        assert_eq!(received.body["init"], 1);
+
+        // Signed for the object's binding, not the namespace it's bound under,
+        // and for the object's name and the request.
+        let signed: SignedPrincipal =
+            serde_json::from_value(received.body["principal"].clone()).unwrap();
+        let digest = codec::request_digest(
+            EnvelopeVersion::of::<Counter>(),
+            &RequestTransport::<_, CounterRequest>::Init { init: 1 },
+        )
+        .unwrap();
+        assert_eq!(signed.object, ObjectRef::Name("visits".to_string()));
+        assert_eq!(
+            keyring.verify(Counter::BINDING, &digest, &signed).unwrap(),
+            Principal::new("fisher")
+        );
+    }
+
+    #[test]
+    fn returns_object_errors() {
+        let envelope = ResponseTransport::<u32, crate::Error>::Error {
+            error: crate::Error::worker("the counter overflowed"),
+        };
+        let (url, gateway) = stand_in(200, serde_json::to_string(&envelope).unwrap());
+        let proxy = HttpProxy::<Counter>::new(url, "visits");
+
+        let error = block_on(proxy.send(CounterRequest::Add(2))).unwrap_err();
+        gateway.join().unwrap();
+
+        assert!(matches!(
+            error,
+            CrateOrObjectError::Object(crate::Error::Worker { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_unsuccessful_responses() {
+        let (url, gateway) = stand_in(502, "bad gateway");
+        let proxy = HttpProxy::<Counter>::new(url, "visits");
+
+        let error = block_on(proxy.init(1)).unwrap_err();
+        gateway.join().unwrap();
+
+        assert!(matches!(
+            error,
+            crate::Error::UnexpectedStatus { status: 502, ref excerpt } if excerpt == "bad gateway"
+        ));
+    }
+}
@@ -0,0 +1,738 @@
+//! JSON-RPC 2.0 encoding of the envelopes in [`crate::transport`], used by
+//! objects whose [`ObjectApi::ENCODING`] is [`crate::Encoding::JsonRpc`].
+//!
+//! A request maps to a JSON-RPC request whose `method` is the tag of the
+//! object's request enum and whose `params` are the variant's body, so
+//! `ObjectApi::Request` must be an externally tagged enum, serde's default.
+//! Unit variants are sent without `params`. Init data is sent as a call to
+//! [`INIT_METHOD`], and an envelope with both init data and a request as a
+//! batch of both calls.
+//!
+//! Responses map to JSON-RPC result and error objects. Errors returned by the
+//! object use [`OBJECT_ERROR_CODE`] and carry the object's error as `data`,
+//! crate errors use the standard codes and carry a [`crate::Error`]. Objects
+//! answer batches with batches and don't answer notifications, i.e. calls
+//! without an `id`, not even when they fail. Only calls that aren't JSON-RPC
+//! requests at all are answered with a `null` id.
+//!
+//! JSON-RPC messages have no room for an [`crate::transport::EnvelopeVersion`],
+//! so requests are treated as coming from a caller on the object's current
+//! API version.
+//!
+//! ```text
+//! --> {"jsonrpc": "2.0", "method": "insert", "params": {"key": "a", "value": "b"}, "id": 1}
+//! <-- {"jsonrpc": "2.0", "result": null, "id": 1}
+//! ```
+use std::{
+    fmt::{self, Display},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::{
+    de::{self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, VariantAccess},
+    forward_to_deserialize_any, Deserialize, Deserializer, Serialize,
+};
+use serde_json::{Map, Value};
+
+use crate::{
+    codec,
+    transport::{RequestTransport, ResponseTransport},
+    ObjectApi,
+};
+
+/// The JSON-RPC version spoken by this module.
+pub const VERSION: &str = "2.0";
+
+/// The method of calls that initialize the object. Their `params` are the
+/// object's `Init`.
+pub const INIT_METHOD: &str = "do_proxy.init";
+
+/// The id of the init calls sent by [`encode_request`]. A response with this id
+/// acknowledges the init rather than answering a request.
+pub const INIT_ID: &str = "init";
+
+/// The error code of errors returned by the object itself.
+pub const OBJECT_ERROR_CODE: i64 = -32000;
+
+/// The body isn't valid JSON.
+pub const PARSE_ERROR: i64 = -32700;
+/// The body isn't a JSON-RPC request.
+pub const INVALID_REQUEST: i64 = -32600;
+/// The object's request type has no variant named like the method.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The params don't match the method's variant, or failed validation.
+pub const INVALID_PARAMS: i64 = -32602;
+/// Any other crate error.
+pub const INTERNAL_ERROR: i64 = -32603;
+
+static CORRELATION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Returns a new id that correlates a request with its response.
+pub fn correlation_id() -> u64 {
+    CORRELATION_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Serialize, Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    method: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    params: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RpcResponse {
+    jsonrpc: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorObject>,
+    id: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ErrorObject {
+    code: i64,
+    message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+}
+
+/// A call decoded by [`decode_requests`].
+pub struct Call<O: ObjectApi> {
+    /// The call's id, `None` for notifications.
+    pub id: Option<Value>,
+    /// The call as an envelope, or the reason it couldn't be decoded.
+    pub request: Result<codec::Request<O>, crate::Error>,
+}
+
+/// The calls in a JSON-RPC request.
+pub struct Calls<O: ObjectApi> {
+    /// Whether the calls were sent as a batch, which must be answered with a
+    /// batch.
+    pub batch: bool,
+    pub calls: Vec<Call<O>>,
+}
+
+/// Encodes a request envelope for `O` as a JSON-RPC request, or as a batch if
+/// it carries both init data and a request.
+pub fn encode_request<O: ObjectApi>(request: &codec::Request<O>) -> Result<String, crate::Error> {
+    let calls = match request {
+        RequestTransport::InitWithRequest { init, request } => {
+            vec![init_call(init)?, request_call(request)?]
+        }
+        RequestTransport::Init { init } => vec![init_call(init)?],
+        RequestTransport::Request { request } => vec![request_call(request)?],
+        RequestTransport::Empty => Vec::new(),
+    };
+
+    match calls.as_slice() {
+        [call] => to_string(call),
+        calls => to_string(calls),
+    }
+}
+
+fn init_call(init: &impl Serialize) -> Result<RpcRequest, crate::Error> {
+    Ok(RpcRequest {
+        jsonrpc: VERSION.to_string(),
+        method: INIT_METHOD.to_string(),
+        params: Some(to_value(init)?),
+        id: Some(INIT_ID.into()),
+    })
+}
+
+fn request_call(request: &impl Serialize) -> Result<RpcRequest, crate::Error> {
+    let (method, params) = codec::split_command(to_value(request)?)?;
+
+    Ok(RpcRequest {
+        jsonrpc: VERSION.to_string(),
+        method,
+        params,
+        id: Some(correlation_id().into()),
+    })
+}
+
+/// Decodes a JSON-RPC response, or batch of responses, sent by `O`. The first
+/// error in a batch is returned, otherwise its last response.
+pub fn decode_response<O: ObjectApi>(body: &str) -> Result<codec::Response<O>, crate::Error> {
+    let value: Value =
+        serde_json::from_str(body).map_err(|err| crate::Error::decode(&err, body))?;
+    let responses = match value {
+        Value::Array(_) => Vec::<RpcResponse>::deserialize(value),
+        value => RpcResponse::deserialize(value).map(|response| vec![response]),
+    }
+    .map_err(|err| crate::Error::decode(&err, body))?;
+
+    let mut last = None;
+    for response in responses {
+        if let Some(error) = response.error {
+            return decode_error::<O>(error, body);
+        }
+
+        last = Some(if response.id == INIT_ID {
+            ResponseTransport::Initialized
+        } else {
+            let response = O::Response::deserialize(response.result.unwrap_or(Value::Null))
+                .map_err(|err| crate::Error::decode(&err, body))?;
+            ResponseTransport::Response { response }
+        });
+    }
+
+    last.ok_or(crate::Error::ExpectedObjectResponse)
+}
+
+fn decode_error<O: ObjectApi>(
+    error: ErrorObject,
+    body: &str,
+) -> Result<codec::Response<O>, crate::Error> {
+    let data = error.data.unwrap_or(Value::Null);
+
+    if error.code == OBJECT_ERROR_CODE {
+        let error = O::Error::deserialize(data).map_err(|err| crate::Error::decode(&err, body))?;
+        return Ok(ResponseTransport::Error { error });
+    }
+
+    let error = crate::Error::deserialize(data)
+        .unwrap_or_else(|_| crate::Error::worker(format!("{} ({})", error.message, error.code)));
+    Ok(ResponseTransport::Failure { error })
+}
+
+/// Decodes a JSON-RPC request, or batch of requests, sent to `O`. Each
+/// request is handed to `upcast` along with `O::API_VERSION`, see
+/// [`codec::decode_request_with`].
+///
+/// Fails only if the body as a whole can't be decoded, calls that can't be
+/// decoded are returned with their error. Answer a failure with
+/// [`encode_failure`].
+pub fn decode_requests<O: ObjectApi>(
+    body: &str,
+    upcast: impl Fn(u32, Value) -> Result<O::Request, crate::Error>,
+) -> Result<Calls<O>, crate::Error> {
+    let value: Value =
+        serde_json::from_str(body).map_err(|err| crate::Error::decode(&err, body))?;
+    let (batch, values) = match value {
+        Value::Array(values) if values.is_empty() => {
+            return Err(crate::Error::ProtocolMismatch {
+                expected: "a JSON-RPC request".to_string(),
+                found: "an empty batch".to_string(),
+            })
+        }
+        Value::Array(values) => (true, values),
+        value => (false, vec![value]),
+    };
+
+    let calls = values
+        .into_iter()
+        .map(|value| decode_call::<O>(value, &upcast))
+        .collect();
+
+    Ok(Calls { batch, calls })
+}
+
+fn decode_call<O: ObjectApi>(
+    value: Value,
+    upcast: &impl Fn(u32, Value) -> Result<O::Request, crate::Error>,
+) -> Call<O> {
+    let call = match RpcRequest::deserialize(&value) {
+        Ok(call) => call,
+        // Calls that aren't requests at all are answered even without an id.
+        Err(err) => {
+            return Call {
+                id: Some(Value::Null),
+                request: Err(crate::Error::ProtocolMismatch {
+                    expected: "a JSON-RPC request".to_string(),
+                    found: err.to_string(),
+                }),
+            }
+        }
+    };
+
+    let id = call.id.clone();
+    let request = decode_method::<O>(call, &value, upcast);
+
+    Call { id, request }
+}
+
+/// Decodes a call's method and params. `value` is the call as it was sent,
+/// for the excerpt of decode errors.
+fn decode_method<O: ObjectApi>(
+    call: RpcRequest,
+    value: &Value,
+    upcast: &impl Fn(u32, Value) -> Result<O::Request, crate::Error>,
+) -> Result<codec::Request<O>, crate::Error> {
+    if call.jsonrpc != VERSION {
+        return Err(crate::Error::ProtocolMismatch {
+            expected: format!("JSON-RPC {VERSION}"),
+            found: format!("JSON-RPC {}", call.jsonrpc),
+        });
+    }
+
+    if call.method == INIT_METHOD {
+        let init = O::Init::deserialize(call.params.unwrap_or(Value::Null))
+            .map_err(|err| crate::Error::decode(&err, &value.to_string()))?;
+        return Ok(RequestTransport::Init { init });
+    }
+
+    if !has_method::<O::Request>(&call.method) {
+        return Err(crate::Error::UnknownCommand {
+            command: call.method,
+        });
+    }
+
+    let request = match call.params {
+        Some(params) => Value::Object(Map::from_iter([(call.method, params)])),
+        None => Value::String(call.method),
+    };
+    let request = upcast(O::API_VERSION, request)?;
+    Ok(RequestTransport::Request { request })
+}
+
+/// Returns `false` if `R` is an enum without a variant named `method`.
+/// Request types that aren't enums can't tell, so they're assumed to have it
+/// and fail when the call is decoded instead.
+fn has_method<R: DeserializeOwned>(method: &str) -> bool {
+    !matches!(
+        R::deserialize(MethodProbe(method)),
+        Err(ProbeError::UnknownVariant)
+    )
+}
+
+/// A deserializer that only ever yields an enum variant named `method`,
+/// without a body.
+struct MethodProbe<'m>(&'m str);
+
+#[derive(Debug)]
+enum ProbeError {
+    UnknownVariant,
+    Other,
+}
+
+impl Display for ProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProbeError::UnknownVariant => f.write_str("unknown variant"),
+            ProbeError::Other => f.write_str("not an enum variant"),
+        }
+    }
+}
+
+impl std::error::Error for ProbeError {}
+
+impl de::Error for ProbeError {
+    fn custom<T: Display>(_msg: T) -> Self {
+        ProbeError::Other
+    }
+
+    fn unknown_variant(_variant: &str, _expected: &'static [&'static str]) -> Self {
+        ProbeError::UnknownVariant
+    }
+}
+
+impl<'de> Deserializer<'de> for MethodProbe<'_> {
+    type Error = ProbeError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, _visitor: V) -> Result<V::Value, ProbeError> {
+        Err(ProbeError::Other)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        visitor.visit_enum(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> EnumAccess<'de> for MethodProbe<'_> {
+    type Error = ProbeError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), ProbeError> {
+        let variant = seed.deserialize(self.0.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+/// The variant exists once its body is asked for, so the probe stops there.
+impl<'de> VariantAccess<'de> for MethodProbe<'_> {
+    type Error = ProbeError;
+
+    fn unit_variant(self) -> Result<(), ProbeError> {
+        Err(ProbeError::Other)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        _seed: T,
+    ) -> Result<T::Value, ProbeError> {
+        Err(ProbeError::Other)
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        _visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        Err(ProbeError::Other)
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, ProbeError> {
+        Err(ProbeError::Other)
+    }
+}
+
+/// Encodes the responses to the calls of a JSON-RPC request, paired with the
+/// calls' ids. Responses to notifications are dropped, so there may be nothing
+/// to send back.
+pub fn encode_responses<Response, Error>(
+    batch: bool,
+    responses: Vec<(Option<Value>, ResponseTransport<Response, Error>)>,
+) -> Result<Option<String>, crate::Error>
+where
+    Response: Serialize,
+    Error: Serialize + Display,
+{
+    let mut responses = responses
+        .into_iter()
+        .filter_map(|(id, response)| Some(rpc_response(id?, response)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match responses.len() {
+        0 => Ok(None),
+        1 if !batch => to_string(&responses.remove(0)).map(Some),
+        _ => to_string(&responses).map(Some),
+    }
+}
+
+/// Encodes the response to a JSON-RPC request that couldn't be decoded at
+/// all, see [`decode_requests`].
+pub fn encode_failure(error: &crate::Error) -> Result<String, crate::Error> {
+    let code = match error {
+        crate::Error::Decode { .. } => PARSE_ERROR,
+        _ => INVALID_REQUEST,
+    };
+
+    to_string(&RpcResponse {
+        jsonrpc: VERSION.to_string(),
+        result: None,
+        error: Some(ErrorObject {
+            code,
+            message: error.to_string(),
+            data: Some(to_value(error)?),
+        }),
+        id: Value::Null,
+    })
+}
+
+fn rpc_response<Response: Serialize, Error: Serialize + Display>(
+    id: Value,
+    response: ResponseTransport<Response, Error>,
+) -> Result<RpcResponse, crate::Error> {
+    let (result, error) = match response {
+        ResponseTransport::Response { response } => (Some(to_value(&response)?), None),
+        ResponseTransport::Initialized => (Some(Value::Null), None),
+        ResponseTransport::Error { error } => {
+            let error = ErrorObject {
+                code: OBJECT_ERROR_CODE,
+                message: error.to_string(),
+                data: Some(to_value(&error)?),
+            };
+            (None, Some(error))
+        }
+        ResponseTransport::Failure { error } => {
+            let code = match error {
+                crate::Error::Decode { .. } | crate::Error::Validation { .. } => INVALID_PARAMS,
+                crate::Error::ProtocolMismatch { .. } | crate::Error::PayloadTooLarge { .. } => {
+                    INVALID_REQUEST
+                }
+                crate::Error::UnknownCommand { .. } => METHOD_NOT_FOUND,
+                _ => INTERNAL_ERROR,
+            };
+            let error = ErrorObject {
+                code,
+                message: error.to_string(),
+                data: Some(to_value(&error)?),
+            };
+            (None, Some(error))
+        }
+    };
+
+    Ok(RpcResponse {
+        jsonrpc: VERSION.to_string(),
+        result,
+        error,
+        id,
+    })
+}
+
+fn to_value(value: &impl Serialize) -> Result<Value, crate::Error> {
+    serde_json::to_value(value).map_err(|err| crate::Error::encode(&err))
+}
+
+fn to_string(value: &(impl Serialize + ?Sized)) -> Result<String, crate::Error> {
+    serde_json::to_string(value).map_err(|err| crate::Error::encode(&err))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::Validate;
+
+    struct Counter;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    enum CounterRequest {
+        Add { amount: u32 },
+        Reset,
+    }
+
+    impl Validate for CounterRequest {}
+
+    impl ObjectApi for Counter {
+        const BINDING: &'static str = "COUNTER";
+
+        type Init = u32;
+        type Request = CounterRequest;
+        type Response = u32;
+        type Error = crate::Error;
+    }
+
+    fn decode(body: &str) -> Calls<Counter> {
+        decode_requests::<Counter>(body, |_, request| {
+            codec::upcast::<CounterRequest, _>(request)
+        })
+        .unwrap()
+    }
+
+    /// Answers every call like an object would: `Initialized` for inits, the
+    /// call's index for requests and a failure for calls that didn't decode.
+    fn answer(calls: Calls<Counter>) -> Option<String> {
+        let responses = calls
+            .calls
+            .into_iter()
+            .enumerate()
+            .map(|(index, call)| {
+                let response = match call.request {
+                    Ok(RequestTransport::Init { .. }) => ResponseTransport::Initialized,
+                    Ok(_) => ResponseTransport::Response {
+                        response: index as u32,
+                    },
+                    Err(error) => ResponseTransport::Failure { error },
+                };
+                (call.id, response)
+            })
+            .collect();
+
+        encode_responses::<u32, crate::Error>(calls.batch, responses).unwrap()
+    }
+
+    fn error_codes(response: &str) -> Vec<(Value, i64)> {
+        let response: Value = serde_json::from_str(response).unwrap();
+        let responses = match response {
+            Value::Array(responses) => responses,
+            response => vec![response],
+        };
+
+        responses
+            .into_iter()
+            .map(|response| {
+                let code = response["error"]["code"].as_i64().unwrap();
+                (response["id"].clone(), code)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_requests() {
+        let request = RequestTransport::Request {
+            request: CounterRequest::Add { amount: 2 },
+        };
+        let body = encode_request::<Counter>(&request).unwrap();
+
+        let encoded: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(encoded["method"], "add");
+        assert_eq!(encoded["params"], json!({ "amount": 2 }));
+
+        let calls = decode(&body);
+        assert!(!calls.batch);
+        assert!(matches!(
+            &calls.calls[0].request,
+            Ok(RequestTransport::Request { request }) if *request == CounterRequest::Add { amount: 2 }
+        ));
+
+        let response = answer(calls).unwrap();
+        assert!(matches!(
+            decode_response::<Counter>(&response).unwrap(),
+            ResponseTransport::Response { response: 0 }
+        ));
+    }
+
+    #[test]
+    fn round_trips_unit_variants() {
+        let request = RequestTransport::Request {
+            request: CounterRequest::Reset,
+        };
+        let body = encode_request::<Counter>(&request).unwrap();
+
+        let encoded: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(encoded["method"], "reset");
+        assert!(encoded.get("params").is_none());
+
+        assert!(matches!(
+            &decode(&body).calls[0].request,
+            Ok(RequestTransport::Request {
+                request: CounterRequest::Reset
+            })
+        ));
+    }
+
+    #[test]
+    fn round_trips_init_and_request_batches() {
+        let request = RequestTransport::InitWithRequest {
+            init: 5,
+            request: CounterRequest::Reset,
+        };
+        let body = encode_request::<Counter>(&request).unwrap();
+
+        let calls = decode(&body);
+        assert!(calls.batch);
+        assert!(matches!(
+            &calls.calls[0],
+            Call { id: Some(id), request: Ok(RequestTransport::Init { init: 5 }) } if id == INIT_ID
+        ));
+        assert!(matches!(
+            &calls.calls[1].request,
+            Ok(RequestTransport::Request {
+                request: CounterRequest::Reset
+            })
+        ));
+
+        let response = answer(calls).unwrap();
+        assert!(response.starts_with('['));
+        assert!(matches!(
+            decode_response::<Counter>(&response).unwrap(),
+            ResponseTransport::Response { response: 1 }
+        ));
+    }
+
+    #[test]
+    fn round_trips_init() {
+        let body = encode_request::<Counter>(&RequestTransport::Init { init: 5 }).unwrap();
+        let response = answer(decode(&body)).unwrap();
+
+        assert!(matches!(
+            decode_response::<Counter>(&response).unwrap(),
+            ResponseTransport::Initialized
+        ));
+    }
+
+    #[test]
+    fn round_trips_object_errors() {
+        let error = crate::Error::worker("overflow");
+        let response = encode_responses::<u32, crate::Error>(
+            false,
+            vec![(
+                Some(json!(1)),
+                ResponseTransport::Error {
+                    error: error.clone(),
+                },
+            )],
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(error_codes(&response), [(json!(1), OBJECT_ERROR_CODE)]);
+        assert!(matches!(
+            decode_response::<Counter>(&response).unwrap(),
+            ResponseTransport::Error { error: decoded } if decoded == error
+        ));
+    }
+
+    #[test]
+    fn answers_unknown_methods_with_method_not_found() {
+        let calls = decode(r#"{"jsonrpc": "2.0", "method": "subtract", "params": {}, "id": 7}"#);
+        assert!(matches!(
+            &calls.calls[0].request,
+            Err(crate::Error::UnknownCommand { command }) if command == "subtract"
+        ));
+
+        let response = answer(calls).unwrap();
+        assert_eq!(error_codes(&response), [(json!(7), METHOD_NOT_FOUND)]);
+    }
+
+    #[test]
+    fn answers_invalid_params() {
+        let calls = decode(r#"{"jsonrpc": "2.0", "method": "add", "params": {}, "id": 7}"#);
+        let response = answer(calls).unwrap();
+
+        assert_eq!(error_codes(&response), [(json!(7), INVALID_PARAMS)]);
+    }
+
+    #[test]
+    fn does_not_answer_notifications() {
+        let body = json!([
+            { "jsonrpc": "2.0", "method": "reset" },
+            { "jsonrpc": "2.0", "method": "subtract" },
+            { "jsonrpc": "2.0", "method": "add", "params": { "amount": "two" } },
+        ]);
+
+        assert_eq!(answer(decode(&body.to_string())), None);
+    }
+
+    #[test]
+    fn answers_calls_that_are_not_requests_with_a_null_id() {
+        let body = json!([
+            1,
+            { "jsonrpc": "2.0", "params": {}, "id": 3 },
+            { "jsonrpc": "2.0", "method": "subtract" },
+            { "jsonrpc": "2.0", "method": "reset", "id": 4 },
+        ]);
+        let response = answer(decode(&body.to_string())).unwrap();
+        let mut responses: Vec<Value> = serde_json::from_str(&response).unwrap();
+
+        assert_eq!(responses.len(), 3);
+        assert_eq!(
+            responses.pop().unwrap(),
+            json!({ "jsonrpc": "2.0", "result": 3, "id": 4 })
+        );
+        assert_eq!(
+            error_codes(&Value::Array(responses).to_string()),
+            [
+                (Value::Null, INVALID_REQUEST),
+                (Value::Null, INVALID_REQUEST)
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bodies_that_are_not_json() {
+        let error = decode_requests::<Counter>("{", |_, request| {
+            codec::upcast::<CounterRequest, _>(request)
+        })
+        .err()
+        .unwrap();
+
+        let response = encode_failure(&error).unwrap();
+        assert_eq!(error_codes(&response), [(Value::Null, PARSE_ERROR)]);
+    }
+}
@@ -0,0 +1,50 @@
+//! # do-proxy-core
+//! The runtime-independent half of
+//! [do-proxy](https://docs.rs/do-proxy/latest/do_proxy/).
+//!
+//! This crate contains the request and response envelopes that are sent
+//! between a caller and a Durable Object, the codec used to (de)serialize
+//! them, the crate's error types and the [`ObjectApi`] trait, which describes
+//! the types an object accepts and returns. None of it depends on
+//! [workers-rs](https://github.com/cloudflare/workers-rs), so crates that share
+//! an object's request and response types can be used from native services
+//! and tests.
+//!
+//! ## Features
+//!
+//! - `worker`: conversions between this crate's errors and `worker::Error`,
+//!   and HTTP responses for [`ProblemDetails`].
+//!   Enabled by `do-proxy`.
+//! - `http-client`: [`HttpProxy`], a native client that talks to objects
+//!   through a gateway worker.
+//! - `schemars`: JSON Schemas for object APIs and envelopes, see [`schema`],
+//!   and wire-compatibility checks between versions of an API, see
+//!   [`compat`].
+//! - `typescript`: TypeScript types and clients for object APIs, see
+//!   [`typescript`].
+pub mod auth;
+pub mod codec;
+#[cfg(feature = "schemars")]
+pub mod compat;
+mod error;
+#[cfg(feature = "http-client")]
+mod http;
+pub mod jsonrpc;
+mod object;
+mod problem;
+#[cfg(feature = "schemars")]
+pub mod schema;
+pub mod transport;
+#[cfg(feature = "typescript")]
+pub mod typescript;
+mod validate;
+
+pub use self::{
+    error::{Cause, CrateOrObjectError, Error},
+    object::{Encoding, ObjectApi},
+    problem::{IntoHttpError, ProblemDetails},
+    validate::{FieldError, Validate, ValidationErrors},
+};
+
+#[cfg(feature = "http-client")]
+pub use self::http::HttpProxy;
@@ -0,0 +1,125 @@
+use std::error::Error;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::Validate;
+
+/// Describes the API of a Durable Object: its binding and the types it
+/// accepts and returns.
+///
+/// Objects implement this trait along with `do_proxy::DoProxy`. Because it
+/// doesn't depend on the Workers runtime, a crate that only contains an
+/// object's API can be shared between the worker that owns the object and
+/// native callers, for example an [`crate::HttpProxy`].
+///
+/// # Example
+///
+/// ```ignore
+/// pub struct PersonApi;
+///
+/// impl ObjectApi for PersonApi {
+///     const BINDING: &'static str = "PERSON_OBJECT";
+///
+///     type Init = NewPerson;
+///     type Request = PersonRequest;
+///     type Response = PersonResponse;
+///     type Error = PersonError;
+/// }
+/// ```
+pub trait ObjectApi {
+    /// The Durable Object's binding. Must be the same as the one written in
+    /// your `wrangler.toml`. For example, `INSERTER_OBJECT`.
+    const BINDING: &'static str;
+
+    /// The version of the object's API, sent with every request. Bump it
+    /// whenever `Request` changes in a way older objects can't decode.
+    /// Requests from callers that predate versioning count as version `0`.
+    const API_VERSION: u32 = 0;
+
+    /// The oldest API version the object still accepts. Together with
+    /// [`Self::API_VERSION`] it forms the range of versions the object
+    /// supports, requests outside of it fail with
+    /// [`crate::Error::ProtocolMismatch`].
+    const MIN_API_VERSION: u32 = 0;
+
+    /// How envelopes sent to and from the object are encoded. Defaults to
+    /// do-proxy's own [`Encoding::Envelope`].
+    const ENCODING: Encoding = Encoding::Envelope;
+
+    /// The initialization data that will be passed to the the object when it is
+    /// first created. This should be used to set data that is expected to
+    /// always be available when the object loads. For example, the first time a
+    /// `Person` object is created, it should be initialized with the person's
+    /// name. This way when `load_from_storage` is called, we can expect a
+    /// stored `name` field to always be present. If it is not present, then the
+    /// object has not yet been initialized and `load_from_storage` should
+    /// error.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// struct NewPerson {
+    ///     name: String,
+    ///     birthday: DateTime<Utc>,
+    /// }
+    /// ```
+    type Init: Serialize + DeserializeOwned + Validate + 'static;
+    /// The request type that will be sent to the object. This is generally an
+    /// enum of all of the different "commands" that the object can handle.
+    ///
+    /// Keep it an externally tagged enum, serde's default, if the object uses
+    /// the JSON-RPC encoding or an authorization policy with command rules.
+    /// Both name commands by variant, see [`crate::codec::command_name`].
+    ///
+    /// Requests are validated before they reach the object, see [`Validate`].
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// enum PersonRequest {
+    ///     GetAge,
+    ///     GetNextBirthday,
+    ///     GetName,
+    /// }
+    /// ```
+    type Request: Serialize + DeserializeOwned + Validate + 'static;
+    /// The response type that will be sent back from the object This is generally
+    /// an enum of all of the different "responses" that the object can send.
+    ///
+    /// Note, types like `Option<serde_json::Value>` will work!
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// enum PersonResponse {
+    ///     Age(usize),
+    ///     Birthday(chrono::DateTime<chrono::Utc>),
+    ///     GetName(String),
+    /// }
+    /// ```
+    type Response: Serialize + DeserializeOwned + 'static;
+
+    /// The error type that will be returned from the object. This lets users
+    /// cleanly (kind of) pass errors from the object back to the caller.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// enum PersonError {
+    ///     NotYetBorn,
+    /// }
+    /// ```
+    type Error: Serialize + DeserializeOwned + Error + 'static;
+}
+
+/// The wire encodings of the envelopes in [`crate::transport`], see
+/// [`ObjectApi::ENCODING`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// do-proxy's own envelopes, see [`crate::codec`].
+    #[default]
+    Envelope,
+    /// JSON-RPC 2.0 requests and responses, see [`crate::jsonrpc`]. Useful
+    /// when the object is called by tools that speak JSON-RPC.
+    JsonRpc,
+}
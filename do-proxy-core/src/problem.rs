@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{CrateOrObjectError, Error};
+
+/// Describes how an error is presented to HTTP clients.
+///
+/// Implement this for an object's [`crate::ObjectApi::Error`] to choose the
+/// status code, error code and message that gateways respond with. Every
+/// method has a default, so an empty impl responds with `400 Bad Request` and
+/// the error's `Display` output.
+///
+/// # Example
+///
+/// ```ignore
+/// impl IntoHttpError for PersonError {
+///     fn status(&self) -> u16 {
+///         match self {
+///             PersonError::NotFound => 404,
+///             PersonError::NotYetBorn => 409,
+///         }
+///     }
+///
+///     fn code(&self) -> &str {
+///         match self {
+///             PersonError::NotFound => "person_not_found",
+///             PersonError::NotYetBorn => "person_not_yet_born",
+///         }
+///     }
+/// }
+/// ```
+pub trait IntoHttpError: std::error::Error {
+    /// The HTTP status code of the response.
+    fn status(&self) -> u16 {
+        400
+    }
+
+    /// A stable, machine-readable code for the error.
+    fn code(&self) -> &str {
+        "object_error"
+    }
+
+    /// The message shown to clients. Override it if the `Display` output
+    /// contains details that shouldn't leave the worker.
+    fn public_message(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Crate errors are internal, so clients only learn the kind of error from its
+/// code, not the details. Errors caused by the client's payload are the
+/// exception.
+impl IntoHttpError for Error {
+    fn status(&self) -> u16 {
+        match self {
+            Error::DeadlineExceeded => 504,
+            Error::Unauthenticated { .. } => 401,
+            Error::Forbidden { .. } => 403,
+            Error::Validation { .. } => 422,
+            Error::PayloadTooLarge { .. } => 413,
+            _ => 500,
+        }
+    }
+
+    /// The same code as [`Error::code`], so clients see one set of codes.
+    fn code(&self) -> &str {
+        Error::code(self)
+    }
+
+    fn public_message(&self) -> String {
+        match self {
+            Error::DeadlineExceeded => "the object did not respond in time".to_string(),
+            Error::Unauthenticated { .. } => "the caller could not be authenticated".to_string(),
+            Error::Forbidden { command, .. } => format!("the caller may not send `{command}`"),
+            Error::Validation { .. } | Error::PayloadTooLarge { .. } => self.to_string(),
+            _ => "an internal error occurred".to_string(),
+        }
+    }
+}
+
+/// A [problem details](https://www.rfc-editor.org/rfc/rfc7807) body, sent
+/// with the `application/problem+json` content type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ProblemDetails {
+    /// A short summary of the status code, e.g. `Not Found`.
+    pub title: String,
+    /// The HTTP status code.
+    pub status: u16,
+    /// A message for the client, see [`IntoHttpError::public_message`].
+    pub detail: String,
+    /// A machine-readable error code, see [`IntoHttpError::code`].
+    pub code: String,
+}
+
+impl ProblemDetails {
+    /// The content type of problem details bodies.
+    pub const CONTENT_TYPE: &'static str = "application/problem+json";
+
+    pub fn new(status: u16, code: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            title: status_title(status).to_string(),
+            status,
+            detail: detail.into(),
+            code: code.into(),
+        }
+    }
+
+    /// Describes `error` as problem details.
+    pub fn from_error<E: IntoHttpError + ?Sized>(error: &E) -> Self {
+        Self::new(error.status(), error.code(), error.public_message())
+    }
+
+    /// Creates a `worker::Response` with this body and status.
+    #[cfg(feature = "worker")]
+    pub fn into_response(self) -> worker::Result<worker::Response> {
+        let mut headers = worker::Headers::new();
+        headers.set("content-type", Self::CONTENT_TYPE)?;
+
+        Ok(worker::Response::from_json(&self)?
+            .with_status(self.status)
+            .with_headers(headers))
+    }
+}
+
+impl<ObjectError: IntoHttpError> CrateOrObjectError<ObjectError> {
+    /// Describes the error as problem details. Object errors are described by
+    /// their [`IntoHttpError`] impl, crate errors stay opaque.
+    pub fn problem(&self) -> ProblemDetails {
+        match self {
+            CrateOrObjectError::Crate(error) => ProblemDetails::from_error(error),
+            CrateOrObjectError::Object(error) => ProblemDetails::from_error(error),
+        }
+    }
+
+    /// Creates an `application/problem+json` response describing the error,
+    /// see [`CrateOrObjectError::problem`].
+    #[cfg(feature = "worker")]
+    pub fn into_response(self) -> worker::Result<worker::Response> {
+        self.problem().into_response()
+    }
+}
+
+fn status_title(status: u16) -> &'static str {
+    match status {
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        410 => "Gone",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        status if status < 500 => "Client Error",
+        _ => "Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crate_errors_use_their_own_codes() {
+        let errors = [
+            Error::Validation { fields: Vec::new() },
+            Error::PayloadTooLarge { size: 2, limit: 1 },
+            Error::DeadlineExceeded,
+            Error::worker("storage is gone"),
+        ];
+
+        for error in errors {
+            assert_eq!(ProblemDetails::from_error(&error).code, error.code());
+        }
+    }
+
+    #[test]
+    fn internal_details_stay_hidden() {
+        let problem = ProblemDetails::from_error(&Error::worker("storage is gone"));
+
+        assert_eq!(problem.status, 500);
+        assert_eq!(problem.code, "worker");
+        assert_eq!(problem.detail, "an internal error occurred");
+    }
+}
@@ -0,0 +1,139 @@
+//! JSON Schemas for object APIs. Requires the `schemars` feature.
+//!
+//! [`object_schemas`] describes everything a caller needs to talk to an
+//! object: its `Init`, `Request`, `Response` and `Error` types and the
+//! envelopes that wrap them on the wire. A [`SchemaRegistry`] collects the
+//! schemas of many objects and writes them to a directory, for example from a
+//! test or a small binary in CI.
+use std::{fs, io, path::Path};
+
+use schemars::{gen::SchemaSettings, schema::RootSchema, JsonSchema};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::SignedPrincipal,
+    transport::{EnvelopeVersion, RequestTransport, ResponseTransport},
+    ObjectApi,
+};
+
+/// The request envelope as it appears on the wire.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct RequestEnvelope<Init, Request> {
+    #[serde(flatten)]
+    version: EnvelopeVersion,
+    #[serde(default)]
+    principal: Option<SignedPrincipal>,
+    #[serde(flatten)]
+    envelope: RequestTransport<Init, Request>,
+}
+
+/// The JSON Schemas of an object's API.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectSchemas {
+    /// The object's [`ObjectApi::BINDING`].
+    pub binding: String,
+    /// The object's [`ObjectApi::API_VERSION`].
+    pub api_version: u32,
+    /// The object's [`ObjectApi::MIN_API_VERSION`].
+    pub min_api_version: u32,
+    pub init: RootSchema,
+    pub request: RootSchema,
+    pub response: RootSchema,
+    pub error: RootSchema,
+    /// The envelope callers send, including its [`EnvelopeVersion`].
+    pub request_envelope: RootSchema,
+    /// The envelope the object sends back.
+    pub response_envelope: RootSchema,
+}
+
+/// Generates the JSON Schemas of `O`'s API.
+///
+/// ```ignore
+/// let schemas = object_schemas::<Inserter>();
+/// println!("{}", serde_json::to_string_pretty(&schemas.request)?);
+/// ```
+pub fn object_schemas<O>() -> ObjectSchemas
+where
+    O: ObjectApi,
+    O::Init: JsonSchema,
+    O::Request: JsonSchema,
+    O::Response: JsonSchema,
+    O::Error: JsonSchema,
+{
+    ObjectSchemas {
+        binding: O::BINDING.to_string(),
+        api_version: O::API_VERSION,
+        min_api_version: O::MIN_API_VERSION,
+        init: schema_for::<O::Init>(),
+        request: schema_for::<O::Request>(),
+        response: schema_for::<O::Response>(),
+        error: schema_for::<O::Error>(),
+        request_envelope: schema_for::<RequestEnvelope<O::Init, O::Request>>(),
+        response_envelope: schema_for::<ResponseTransport<O::Response, O::Error>>(),
+    }
+}
+
+/// Collects the schemas of many objects.
+///
+/// # Example
+///
+/// ```ignore
+/// #[test]
+/// fn export_schemas() {
+///     SchemaRegistry::new()
+///         .object::<Inserter>()
+///         .object::<Person>()
+///         .write_to_dir("schemas")
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    objects: Vec<ObjectSchemas>,
+}
+
+impl SchemaRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the object `O`.
+    pub fn object<O>(mut self) -> Self
+    where
+        O: ObjectApi,
+        O::Init: JsonSchema,
+        O::Request: JsonSchema,
+        O::Response: JsonSchema,
+        O::Error: JsonSchema,
+    {
+        self.objects.push(object_schemas::<O>());
+        self
+    }
+
+    /// The schemas of all registered objects.
+    pub fn objects(&self) -> &[ObjectSchemas] {
+        &self.objects
+    }
+
+    /// Writes the schemas of every registered object to `{dir}/{BINDING}.json`,
+    /// creating `dir` if it doesn't exist.
+    pub fn write_to_dir(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        for schemas in &self.objects {
+            let json = serde_json::to_string_pretty(schemas)?;
+            fs::write(dir.join(format!("{}.json", schemas.binding)), json + "\n")?;
+        }
+
+        Ok(())
+    }
+}
+
+fn schema_for<T: JsonSchema>() -> RootSchema {
+    SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<T>()
+}
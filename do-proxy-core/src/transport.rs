@@ -0,0 +1,200 @@
+//! The envelopes that are sent between a caller and an object.
+use serde::{Deserialize, Serialize};
+
+use crate::{CrateOrObjectError, ObjectApi};
+
+/// The header that carries the binding of the object a forwarded request is
+/// meant for.
+pub const BINDING_HEADER: &str = "x-do-proxy-binding";
+
+/// The header that carries the name of the object a forwarded request is meant
+/// for.
+pub const NAME_HEADER: &str = "x-do-proxy-name";
+
+/// The header that marks a request whose body is a [`RequestTransport`].
+/// Objects hand requests without it to `DoProxy::handle_raw`, unless their
+/// body is an envelope anyway, see [`crate::codec::is_request_envelope`].
+pub const ENVELOPE_HEADER: &str = "x-do-proxy-envelope";
+
+/// The header that carries the JSON-encoded init data of a plain JSON
+/// request, see `DoProxy::PLAIN_JSON`. The `init` query parameter can be used
+/// instead.
+pub const INIT_HEADER: &str = "x-do-proxy-init";
+
+/// The version of the envelopes in this module. Bumped whenever their wire
+/// format changes.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The versions sent alongside every [`RequestTransport`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EnvelopeVersion {
+    /// The version of the envelope itself, see [`PROTOCOL_VERSION`].
+    #[serde(default = "default_protocol")]
+    pub protocol: u32,
+    /// The caller's `ObjectApi::API_VERSION`.
+    #[serde(default)]
+    pub api_version: u32,
+}
+
+impl EnvelopeVersion {
+    /// The versions sent by callers of `O`.
+    pub fn of<O: ObjectApi>() -> Self {
+        Self {
+            protocol: PROTOCOL_VERSION,
+            api_version: O::API_VERSION,
+        }
+    }
+
+    /// Checks that the object `O` understands requests sent with these
+    /// versions.
+    pub fn check<O: ObjectApi>(&self) -> Result<(), crate::Error> {
+        if self.protocol != PROTOCOL_VERSION {
+            return Err(crate::Error::ProtocolMismatch {
+                expected: format!("protocol {PROTOCOL_VERSION}"),
+                found: format!("protocol {}", self.protocol),
+            });
+        }
+
+        if !(O::MIN_API_VERSION..=O::API_VERSION).contains(&self.api_version) {
+            return Err(crate::Error::ProtocolMismatch {
+                expected: format!("api version {}..={}", O::MIN_API_VERSION, O::API_VERSION),
+                found: format!("api version {}", self.api_version),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Envelopes without a protocol version were sent before versioning, which
+/// didn't change the wire format.
+fn default_protocol() -> u32 {
+    PROTOCOL_VERSION
+}
+
+/// The envelope a caller sends to an object. On the wire, it's accompanied
+/// by an [`EnvelopeVersion`], see [`crate::codec`].
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum RequestTransport<Init, Request> {
+    InitWithRequest {
+        init: Init,
+        request: Request,
+    },
+    Init {
+        init: Init,
+    },
+    Request {
+        request: Request,
+    },
+    #[doc(hidden)]
+    #[serde(skip)]
+    Empty,
+}
+
+impl<Init, Request> RequestTransport<Init, Request> {
+    /// Takes the init data out of the envelope, leaving only the request, if
+    /// there is one.
+    pub fn take_init(&mut self) -> Option<Init> {
+        let this = std::mem::replace(self, RequestTransport::Empty);
+
+        match this {
+            RequestTransport::Init { init } => {
+                *self = RequestTransport::Empty;
+                Some(init)
+            }
+            RequestTransport::Request { request } => {
+                *self = RequestTransport::Request { request };
+                None
+            }
+            RequestTransport::InitWithRequest { init, request } => {
+                *self = RequestTransport::Request { request };
+                Some(init)
+            }
+            RequestTransport::Empty => None,
+        }
+    }
+
+    /// Converts the request in the envelope, if there is one.
+    pub fn try_map_request<R, E>(
+        self,
+        f: impl FnOnce(Request) -> Result<R, E>,
+    ) -> Result<RequestTransport<Init, R>, E> {
+        Ok(match self {
+            RequestTransport::InitWithRequest { init, request } => {
+                RequestTransport::InitWithRequest {
+                    init,
+                    request: f(request)?,
+                }
+            }
+            RequestTransport::Init { init } => RequestTransport::Init { init },
+            RequestTransport::Request { request } => RequestTransport::Request {
+                request: f(request)?,
+            },
+            RequestTransport::Empty => RequestTransport::Empty,
+        })
+    }
+}
+
+/// The envelope an object sends back to a caller.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ResponseTransport<Response, Error> {
+    Response {
+        response: Response,
+    },
+    Error {
+        error: Error,
+    },
+    Initialized,
+    /// The object failed before it could handle the request, for example
+    /// because the envelope couldn't be decoded.
+    Failure {
+        error: crate::Error,
+    },
+}
+
+impl<Response, Error> ResponseTransport<Response, Error> {
+    /// Unwraps the response to a request.
+    pub fn into_response(self) -> Result<Response, CrateOrObjectError<Error>> {
+        match self {
+            ResponseTransport::Response { response } => Ok(response),
+            ResponseTransport::Error { error } => Err(CrateOrObjectError::Object(error)),
+            ResponseTransport::Initialized => Err(crate::Error::ExpectedObjectResponse.into()),
+            ResponseTransport::Failure { error } => Err(error.into()),
+        }
+    }
+
+    /// Unwraps the response to an init request.
+    pub fn into_initialized(self) -> Result<Result<(), Error>, crate::Error> {
+        match self {
+            ResponseTransport::Initialized => Ok(Ok(())),
+            ResponseTransport::Response { .. } => Err(crate::Error::ExpectedObjectInitialized),
+            ResponseTransport::Error { error } => Ok(Err(error)),
+            ResponseTransport::Failure { error } => Err(error),
+        }
+    }
+}
+
+/// A frame an object sends to a caller over a WebSocket session.
+///
+/// Callers send `RequestTransport::Request` frames to the object. Every request
+/// is answered with a `Response`, `Error` or `Failure` frame, unless the object
+/// chooses not to reply. `Event` frames are pushed by the object at any time.
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum SocketMessage<Response, Error> {
+    /// The object's response to a request.
+    Response { response: Response },
+    /// The object failed to handle a request.
+    Error { error: Error },
+    /// The object couldn't make sense of a frame.
+    Failure { error: crate::Error },
+    /// An event pushed by the object.
+    Event { event: Response },
+}
@@ -0,0 +1,503 @@
+//! TypeScript types and clients for object APIs. Requires the `typescript`
+//! feature.
+//!
+//! [`module`] turns an object's [`ObjectSchemas`] into a self-contained
+//! TypeScript module: a type for each of the object's `Init`, `Request`,
+//! `Response` and `Error` types and its envelopes, and a typed client that
+//! sends the same envelopes as a `do_proxy::Proxy`. TypeScript workers can use
+//! the client with a stub of the object:
+//!
+//! ```ts
+//! import { InserterObjectClient } from "./INSERTER_OBJECT";
+//!
+//! const client = new InserterObjectClient(env.INSERTER_OBJECT.get(id));
+//! const response = await client.send({ get: { key: "hello" } });
+//! ```
+//!
+//! Only the parts of JSON Schema that schemars generates are supported.
+//! Anything else is typed as `unknown`. Definitions that would end up with the
+//! same TypeScript name, e.g. two types named `Item` from different modules,
+//! are reported as a [`NameCollision`] rather than silently dropping one.
+use std::{collections::BTreeMap, fs, io, path::Path};
+
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use serde_json::Value;
+
+use crate::{
+    schema::{ObjectSchemas, SchemaRegistry},
+    transport::{ENVELOPE_HEADER, PROTOCOL_VERSION},
+};
+
+/// The typed client, see [`module`].
+const CLIENT_TEMPLATE: &str = include_str!("../templates/client.ts");
+
+/// Names that can't be used for generated types because they would shadow
+/// TypeScript's globals. Definitions with these names get a `Type` suffix.
+const RESERVED: &[&str] = &[
+    "Array", "Boolean", "Date", "Error", "Map", "Number", "Object", "Promise", "Record", "Set",
+    "String",
+];
+
+/// The names of the types and classes exported by the client template.
+const CLIENT_EXPORTS: &[&str] = &["Stub", "ObjectError", "Failure", "Client"];
+
+/// Two definitions that would be generated as the same TypeScript type.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("`{first}` and `{second}` would both be generated as the TypeScript type `{name}`")]
+pub struct NameCollision {
+    /// The TypeScript type name.
+    pub name: String,
+    pub first: String,
+    pub second: String,
+}
+
+/// Generates a TypeScript module with the types of an object's API and a
+/// typed client. Types are prefixed with the object's binding in PascalCase,
+/// e.g. `InserterObjectRequest` for `INSERTER_OBJECT`.
+///
+/// Fails if two different definitions, or a definition and one of the
+/// generated types, would get the same name.
+pub fn module(schemas: &ObjectSchemas) -> Result<String, NameCollision> {
+    let prefix = pascal_case(&schemas.binding);
+    let roots = [
+        ("Init", &schemas.init),
+        ("Request", &schemas.request),
+        ("Response", &schemas.response),
+        ("Error", &schemas.error),
+        ("RequestEnvelope", &schemas.request_envelope),
+        ("ResponseEnvelope", &schemas.response_envelope),
+    ];
+
+    let mut out = String::from("// Generated by do-proxy. Do not edit.\n");
+
+    let mut generated: BTreeMap<_, _> = roots
+        .iter()
+        .map(|(name, _)| *name)
+        .chain(CLIENT_EXPORTS.iter().copied())
+        .map(|name| {
+            let name = format!("{prefix}{name}");
+            let source = format!("{name} (generated)");
+            (name, source)
+        })
+        .collect();
+
+    // The same definition shows up in every root that uses it, so only
+    // definitions that differ collide.
+    let mut definitions: BTreeMap<String, (&String, &Schema)> = BTreeMap::new();
+    for (_, root) in &roots {
+        for (name, schema) in &root.definitions {
+            let ts_name = type_name(name);
+            let first = match (definitions.get(&ts_name), generated.remove(&ts_name)) {
+                (Some((first, existing)), _) if *first == name && *existing == schema => continue,
+                (Some((first, _)), _) => first.to_string(),
+                (None, Some(generated)) => generated,
+                (None, None) => {
+                    definitions.insert(ts_name, (name, schema));
+                    continue;
+                }
+            };
+
+            return Err(NameCollision {
+                name: ts_name,
+                first,
+                second: name.clone(),
+            });
+        }
+    }
+
+    for (name, root) in &roots {
+        out.push('\n');
+        push_type(&mut out, &format!("{prefix}{name}"), &root_schema(root));
+    }
+
+    for (name, (_, schema)) in definitions {
+        out.push('\n');
+        push_type(&mut out, &name, schema);
+    }
+
+    out.push('\n');
+    out.push_str(
+        &CLIENT_TEMPLATE
+            .replace("$PREFIX", &prefix)
+            .replace("$BINDING", &schemas.binding)
+            .replace("$API_VERSION", &schemas.api_version.to_string())
+            .replace("$PROTOCOL_VERSION", &PROTOCOL_VERSION.to_string())
+            .replace("$ENVELOPE_HEADER", ENVELOPE_HEADER),
+    );
+
+    Ok(out)
+}
+
+impl SchemaRegistry {
+    /// Writes a TypeScript module for every registered object to
+    /// `{dir}/{BINDING}.ts`, creating `dir` if it doesn't exist. See
+    /// [`module`], name collisions fail with [`io::ErrorKind::InvalidData`].
+    pub fn write_typescript_to_dir(&self, dir: impl AsRef<Path>) -> io::Result<()> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        for schemas in self.objects() {
+            let module =
+                module(schemas).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            fs::write(dir.join(format!("{}.ts", schemas.binding)), module)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn root_schema(root: &RootSchema) -> Schema {
+    Schema::Object(root.schema.clone())
+}
+
+fn push_type(out: &mut String, name: &str, schema: &Schema) {
+    if let Some(description) = description(schema) {
+        push_doc(out, description, "");
+    }
+
+    out.push_str(&format!("export type {name} = {};\n", ts_type(schema, "")));
+}
+
+fn push_doc(out: &mut String, description: &str, indent: &str) {
+    out.push_str(&format!("{indent}/**\n"));
+    for line in description.lines() {
+        out.push_str(format!("{indent} * {line}").trim_end());
+        out.push('\n');
+    }
+    out.push_str(&format!("{indent} */\n"));
+}
+
+fn description(schema: &Schema) -> Option<&str> {
+    match schema {
+        Schema::Object(object) => object.metadata.as_ref()?.description.as_deref(),
+        Schema::Bool(_) => None,
+    }
+}
+
+/// Converts `schema` to a TypeScript type. `indent` is the indentation of the
+/// line the type starts on.
+fn ts_type(schema: &Schema, indent: &str) -> String {
+    match schema {
+        Schema::Bool(true) => "unknown".to_string(),
+        Schema::Bool(false) => "never".to_string(),
+        Schema::Object(object) => object_type(object, indent),
+    }
+}
+
+fn object_type(schema: &SchemaObject, indent: &str) -> String {
+    if let Some(reference) = &schema.reference {
+        return type_name(reference.trim_start_matches("#/definitions/"));
+    }
+
+    if let Some(value) = &schema.const_value {
+        return literal(value);
+    }
+
+    if let Some(values) = &schema.enum_values {
+        return union(values.iter().map(literal).collect());
+    }
+
+    let mut parts = Vec::new();
+
+    let instance_types = match &schema.instance_type {
+        Some(SingleOrVec::Single(instance_type)) => vec![**instance_type],
+        Some(SingleOrVec::Vec(instance_types)) => instance_types.clone(),
+        None if schema.object.is_some() => vec![InstanceType::Object],
+        None => Vec::new(),
+    };
+    let instance_types: Vec<_> = instance_types
+        .into_iter()
+        .map(|instance_type| match instance_type {
+            InstanceType::Null => "null".to_string(),
+            InstanceType::Boolean => "boolean".to_string(),
+            InstanceType::Number | InstanceType::Integer => "number".to_string(),
+            InstanceType::String => "string".to_string(),
+            InstanceType::Array => array_type(schema, indent),
+            InstanceType::Object => properties_type(schema, indent),
+        })
+        .collect();
+    if !instance_types.is_empty() {
+        parts.push(union(instance_types));
+    }
+
+    if let Some(subschemas) = &schema.subschemas {
+        for alternatives in [&subschemas.one_of, &subschemas.any_of]
+            .into_iter()
+            .flatten()
+        {
+            let alternatives = alternatives
+                .iter()
+                .map(|schema| ts_type(schema, indent))
+                .collect();
+            parts.push(union(alternatives));
+        }
+
+        if let Some(all_of) = &subschemas.all_of {
+            parts.extend(all_of.iter().map(|schema| ts_type(schema, indent)));
+        }
+    }
+
+    match parts.len() {
+        0 => "unknown".to_string(),
+        1 => parts.remove(0),
+        _ => parts
+            .into_iter()
+            .map(|part| parenthesize(&part))
+            .collect::<Vec<_>>()
+            .join(" & "),
+    }
+}
+
+fn array_type(schema: &SchemaObject, indent: &str) -> String {
+    match schema.array.as_ref().and_then(|array| array.items.as_ref()) {
+        Some(SingleOrVec::Single(items)) => format!("Array<{}>", ts_type(items, indent)),
+        Some(SingleOrVec::Vec(items)) => {
+            let items: Vec<_> = items.iter().map(|item| ts_type(item, indent)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        None => "Array<unknown>".to_string(),
+    }
+}
+
+fn properties_type(schema: &SchemaObject, indent: &str) -> String {
+    let Some(object) = &schema.object else {
+        return "Record<string, unknown>".to_string();
+    };
+
+    if object.properties.is_empty() {
+        return match &object.additional_properties {
+            Some(additional) => format!("Record<string, {}>", ts_type(additional, indent)),
+            None if schema.subschemas.is_some() => "{}".to_string(),
+            None => "Record<string, unknown>".to_string(),
+        };
+    }
+
+    let inner = format!("{indent}  ");
+    let mut out = String::from("{\n");
+    for (name, property) in &object.properties {
+        if let Some(description) = description(property) {
+            push_doc(&mut out, description, &inner);
+        }
+
+        let optional = if object.required.contains(name) {
+            ""
+        } else {
+            "?"
+        };
+        out.push_str(&format!(
+            "{inner}{}{optional}: {};\n",
+            property_name(name),
+            ts_type(property, &inner)
+        ));
+    }
+    out.push_str(indent);
+    out.push('}');
+
+    out
+}
+
+fn union(mut types: Vec<String>) -> String {
+    types.dedup();
+    match types.len() {
+        0 => "never".to_string(),
+        1 => types.remove(0),
+        _ => types.join(" | "),
+    }
+}
+
+fn parenthesize(ty: &str) -> String {
+    if ty.contains(" | ") {
+        format!("({ty})")
+    } else {
+        ty.to_string()
+    }
+}
+
+fn literal(value: &Value) -> String {
+    value.to_string()
+}
+
+fn property_name(name: &str) -> String {
+    let is_identifier = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+
+    if is_identifier {
+        name.to_string()
+    } else {
+        Value::from(name).to_string()
+    }
+}
+
+/// Turns a schemars definition name, e.g. `Option_for_String`, into a
+/// TypeScript type name.
+fn type_name(name: &str) -> String {
+    let name = pascal_case(name);
+    if RESERVED.contains(&name.as_str()) {
+        format!("{name}Type")
+    } else {
+        name
+    }
+}
+
+fn pascal_case(name: &str) -> String {
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().map(|c| c.to_ascii_uppercase());
+            let rest: String = if word.chars().all(|c| !c.is_ascii_lowercase()) {
+                chars.flat_map(char::to_lowercase).collect()
+            } else {
+                chars.collect()
+            };
+            first.into_iter().chain(rest.chars()).collect::<String>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{schema::object_schemas, ObjectApi};
+
+    mod a {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        pub struct Item {
+            pub key: String,
+        }
+    }
+
+    mod b {
+        use super::*;
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        pub struct Item {
+            pub count: u32,
+        }
+    }
+
+    /// A type named like one of TypeScript's globals.
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    struct Date {
+        day: u8,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(rename_all = "camelCase")]
+    enum Request {
+        Get { key: String },
+        Put(a::Item),
+        Clear,
+    }
+
+    impl crate::Validate for Request {}
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(rename_all = "camelCase")]
+    struct Response {
+        item: Option<a::Item>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        updated: Option<Date>,
+        #[serde(rename = "content-type")]
+        content_type: String,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    struct ClashingResponse {
+        item: b::Item,
+    }
+
+    /// Named like the generated client of an object bound under `SAMPLE`.
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    struct SampleClient {
+        url: String,
+    }
+
+    struct Api<Resp>(std::marker::PhantomData<Resp>);
+
+    impl<Resp> ObjectApi for Api<Resp>
+    where
+        Resp: Serialize + serde::de::DeserializeOwned + 'static,
+    {
+        const BINDING: &'static str = "SAMPLE";
+
+        type Init = ();
+        type Request = Request;
+        type Response = Resp;
+        type Error = crate::Error;
+    }
+
+    fn module_for<Resp>() -> Result<String, NameCollision>
+    where
+        Resp: Serialize + serde::de::DeserializeOwned + JsonSchema + 'static,
+    {
+        module(&object_schemas::<Api<Resp>>())
+    }
+
+    fn sample() -> String {
+        module_for::<Response>().unwrap()
+    }
+
+    #[test]
+    fn generates_unions_for_enums() {
+        let module = sample();
+
+        assert!(module.contains(
+            "export type SampleRequest = \"clear\" | {\n  get: {\n    key: string;\n  };\n} \
+            | {\n  put: Item;\n};"
+        ));
+        assert!(module.contains("export type Item = {\n  key: string;\n};"));
+    }
+
+    #[test]
+    fn marks_optional_fields() {
+        let module = sample();
+
+        assert!(module.contains("  item?: Item | null;\n"));
+        assert!(module.contains("  updated?: DateType | null;\n"));
+    }
+
+    #[test]
+    fn renames_reserved_identifiers() {
+        let module = sample();
+
+        assert!(module.contains("export type DateType = {\n  day: number;\n};"));
+        assert!(!module.contains("export type Date "));
+        assert!(module.contains("  \"content-type\": string;\n"));
+    }
+
+    #[test]
+    fn emits_shared_definitions_once() {
+        let module = sample();
+
+        assert_eq!(module.matches("export type Item =").count(), 1);
+    }
+
+    #[test]
+    fn reports_colliding_definitions() {
+        let collision = module_for::<ClashingResponse>().unwrap_err();
+
+        assert_eq!(collision.name, "Item");
+        assert_eq!(collision.first, "Item");
+        assert_eq!(collision.second, "Item");
+    }
+
+    #[test]
+    fn reports_collisions_with_generated_types() {
+        let collision = module_for::<SampleClient>().unwrap_err();
+
+        assert_eq!(collision.name, "SampleClient");
+        assert_eq!(collision.first, "SampleClient (generated)");
+        assert_eq!(collision.second, "SampleClient");
+    }
+}
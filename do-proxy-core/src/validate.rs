@@ -0,0 +1,271 @@
+use serde::{Deserialize, Serialize};
+
+/// Validates a request or init payload before it reaches the object.
+///
+/// Every [`crate::ObjectApi::Request`] and [`crate::ObjectApi::Init`]
+/// implements it. `do_proxy::DoProxy` validates both before the object is
+/// loaded and rejects invalid payloads with [`crate::Error::Validation`].
+///
+/// By default, every payload is valid, so types without rules opt out with
+/// an empty impl:
+///
+/// ```ignore
+/// impl Validate for CounterRequest {}
+/// ```
+///
+/// # Example
+///
+/// ```ignore
+/// impl Validate for PersonRequest {
+///     fn validate(&self) -> Result<(), ValidationErrors> {
+///         let mut errors = ValidationErrors::new();
+///         if let PersonRequest::Rename { name } = self {
+///             if name.is_empty() {
+///                 errors.add("name", "must not be empty");
+///             }
+///         }
+///         errors.into_result()
+///     }
+/// }
+/// ```
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        Ok(())
+    }
+}
+
+macro_rules! always_valid {
+    ($($ty:ty),*) => {
+        $(impl Validate for $ty {})*
+    };
+}
+
+always_valid!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    String,
+    serde_json::Value
+);
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self {
+            Some(value) => value.validate(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: Validate> Validate for Box<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        T::validate(self)
+    }
+}
+
+/// Items are nested under their index, e.g. `1.name`.
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        for (index, item) in self.iter().enumerate() {
+            errors.nest(&index.to_string(), item.validate());
+        }
+        errors.into_result()
+    }
+}
+
+/// A problem with one field of a payload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct FieldError {
+    /// The path of the field, e.g. `address.city`.
+    pub field: String,
+    pub message: String,
+}
+
+/// The problems found by [`Validate::validate`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationErrors {
+    fields: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a problem with `field`.
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.fields.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Records the problems of a nested payload under `field`, e.g. `city`
+    /// nested under `address` becomes `address.city`.
+    pub fn nest(&mut self, field: &str, result: Result<(), ValidationErrors>) {
+        let Err(nested) = result else {
+            return;
+        };
+
+        for error in nested.fields {
+            self.add(format!("{field}.{}", error.field), error.message);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// The recorded problems.
+    pub fn fields(&self) -> &[FieldError] {
+        &self.fields
+    }
+
+    /// Returns `Ok(())` if no problems were recorded.
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl From<ValidationErrors> for crate::Error {
+    fn from(errors: ValidationErrors) -> Self {
+        crate::Error::Validation {
+            fields: errors.fields,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(field: &str, message: &str) -> FieldError {
+        FieldError {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn address(city: &str) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        if city.is_empty() {
+            errors.add("city", "must not be empty");
+        }
+        errors.into_result()
+    }
+
+    #[test]
+    fn into_result_is_ok_without_problems() {
+        assert_eq!(ValidationErrors::new().into_result(), Ok(()));
+
+        let mut errors = ValidationErrors::new();
+        errors.add("name", "must not be empty");
+        let errors = errors.into_result().unwrap_err();
+        assert_eq!(errors.fields(), [field("name", "must not be empty")]);
+    }
+
+    #[test]
+    fn nest_prefixes_nested_fields() {
+        let mut errors = ValidationErrors::new();
+        errors.add("name", "must not be empty");
+        errors.nest("address", address(""));
+        errors.nest("billing", address("Berlin"));
+
+        assert_eq!(
+            errors.fields(),
+            [
+                field("name", "must not be empty"),
+                field("address.city", "must not be empty"),
+            ]
+        );
+    }
+
+    #[test]
+    fn nest_ignores_valid_payloads() {
+        let mut errors = ValidationErrors::new();
+        errors.nest("address", address("Berlin"));
+
+        assert!(errors.is_empty());
+        assert_eq!(errors.into_result(), Ok(()));
+    }
+
+    #[test]
+    fn nest_builds_paths_across_levels() {
+        let mut customer = ValidationErrors::new();
+        customer.nest("address", address(""));
+
+        let mut order = ValidationErrors::new();
+        order.nest("customer", customer.into_result());
+
+        assert_eq!(
+            order.fields(),
+            [field("customer.address.city", "must not be empty")]
+        );
+    }
+
+    #[test]
+    fn converts_to_a_validation_error() {
+        let mut errors = ValidationErrors::new();
+        errors.add("name", "must not be empty");
+
+        assert_eq!(
+            crate::Error::from(errors),
+            crate::Error::Validation {
+                fields: vec![field("name", "must not be empty")],
+            }
+        );
+    }
+
+    struct City(&'static str);
+
+    impl Validate for City {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            address(self.0)
+        }
+    }
+
+    #[test]
+    fn payloads_are_valid_by_default() {
+        struct Empty;
+        impl Validate for Empty {}
+
+        assert_eq!(Empty.validate(), Ok(()));
+        assert_eq!(().validate(), Ok(()));
+        assert_eq!(serde_json::json!({ "city": "" }).validate(), Ok(()));
+    }
+
+    #[test]
+    fn containers_validate_their_items() {
+        assert_eq!(None::<City>.validate(), Ok(()));
+        assert!(Some(City("")).validate().is_err());
+
+        let cities = vec![City("Berlin"), City(""), City("")];
+        assert_eq!(
+            cities.validate().unwrap_err().fields(),
+            [
+                field("1.city", "must not be empty"),
+                field("2.city", "must not be empty"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+/**
+ * The subset of a Durable Object stub used by `$PREFIXClient`.
+ */
+export interface $PREFIXStub {
+  fetch(url: string, init: RequestInit): Promise<Response>;
+}
+
+/**
+ * Thrown by `$PREFIXClient` when the object returned an error.
+ */
+export class $PREFIXObjectError extends Error {
+  constructor(readonly error: $PREFIXError) {
+    super("object error: " + JSON.stringify(error));
+  }
+}
+
+/**
+ * Thrown by `$PREFIXClient` when do-proxy failed to deliver or handle a
+ * request.
+ */
+export class $PREFIXFailure extends Error {
+  constructor(readonly error: Extract<$PREFIXResponseEnvelope, { type: "failure" }>["error"] | string) {
+    super("do-proxy: " + (typeof error === "string" ? error : error.code));
+  }
+}
+
+/**
+ * A typed client for the object bound under `$BINDING`. Sends the same
+ * envelopes as a Rust `do_proxy::Proxy`.
+ */
+export class $PREFIXClient {
+  static readonly BINDING = "$BINDING";
+  static readonly API_VERSION = $API_VERSION;
+
+  constructor(private readonly stub: $PREFIXStub) {}
+
+  /** Sends a request to the object. */
+  async send(request: $PREFIXRequest): Promise<$PREFIXResponse> {
+    return responseOf(await this.post({ type: "request", request }));
+  }
+
+  /** Initializes the object. */
+  async init(init: $PREFIXInit): Promise<void> {
+    const envelope = await this.post({ type: "init", init });
+    if (envelope.type !== "initialized") {
+      responseOf(envelope);
+      throw new $PREFIXFailure("expected object to be initialized");
+    }
+  }
+
+  /** Initializes the object and sends it a request. */
+  async initAndSend(init: $PREFIXInit, request: $PREFIXRequest): Promise<$PREFIXResponse> {
+    return responseOf(await this.post({ type: "initWithRequest", init, request }));
+  }
+
+  private async post(envelope: $PREFIXRequestEnvelope): Promise<$PREFIXResponseEnvelope> {
+    const response = await this.stub.fetch(`http://${$PREFIXClient.BINDING}/`, {
+      method: "POST",
+      headers: { "$ENVELOPE_HEADER": "1" },
+      body: JSON.stringify({
+        ...envelope,
+        protocol: $PROTOCOL_VERSION,
+        apiVersion: $PREFIXClient.API_VERSION,
+      }),
+    });
+
+    if (!response.ok) {
+      throw new $PREFIXFailure(`object responded with ${response.status}: ${await response.text()}`);
+    }
+
+    return response.json();
+  }
+}
+
+function responseOf(envelope: $PREFIXResponseEnvelope): $PREFIXResponse {
+  switch (envelope.type) {
+    case "response":
+      return envelope.response;
+    case "error":
+      throw new $PREFIXObjectError(envelope.error);
+    case "failure":
+      throw new $PREFIXFailure(envelope.error);
+    default:
+      throw new $PREFIXFailure("expected object response");
+  }
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    auth::{Keyring, Principal, Signer},
+    codec,
+    proxy::signed_object,
+    transport::{EnvelopeVersion, RequestTransport, ResponseTransport, PROTOCOL_VERSION},
+    CrateOrObjectError, IntoHttpError, StubTransport, Transport,
+};
+
+/// The response envelope received by a [`DynProxy`].
+pub type DynResponse = ResponseTransport<Value, DynError>;
+
+/// An error returned by an object to a [`DynProxy`], in whatever shape the
+/// object's `Error` type is serialized.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DynError(pub Value);
+
+impl fmt::Display for DynError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for DynError {}
+
+impl IntoHttpError for DynError {}
+
+/// An untyped proxy to an object, for tools that talk to objects whose types
+/// they don't know at compile time, like an admin console. Created with
+/// [`crate::EnvExt::dyn_obj`].
+///
+/// Requests and responses are [`serde_json::Value`]s, wrapped in the same
+/// envelopes as the ones sent by a [`crate::Proxy`].
+///
+/// # Example
+///
+/// ```ignore
+/// let proxy = env.dyn_obj("INSERTER_OBJECT", "test_do")?;
+/// let value = proxy.send(json!({ "get": { "key": "hello" } })).await?;
+/// ```
+pub struct DynProxy<T = StubTransport> {
+    transport: T,
+    binding: String,
+    api_version: u32,
+    signer: Option<Signer>,
+}
+
+impl<T: Transport> DynProxy<T> {
+    /// Creates a proxy that delivers its requests with `transport` to objects
+    /// bound under `binding`.
+    pub fn with_transport(transport: T, binding: impl Into<String>) -> Self {
+        Self {
+            transport,
+            binding: binding.into(),
+            api_version: 0,
+            signer: None,
+        }
+    }
+
+    /// Sets the API version sent with every request, see
+    /// [`crate::ObjectApi::API_VERSION`]. Defaults to `0`.
+    pub fn with_api_version(mut self, api_version: u32) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    /// Sends every request on behalf of `principal`, signed with `keyring`,
+    /// see [`crate::Proxy::with_principal`]. Signatures are bound to the
+    /// proxy's binding, which must be the object's
+    /// [`crate::ObjectApi::BINDING`] for the object to accept them, and to the
+    /// transport's [`crate::Transport::object`].
+    pub fn with_principal(
+        mut self,
+        principal: Principal,
+        keyring: &Keyring,
+    ) -> Result<Self, crate::Error> {
+        self.signer = Some(Signer::new(principal, keyring)?);
+        Ok(self)
+    }
+
+    /// The principal requests are sent on behalf of, see
+    /// [`DynProxy::with_principal`].
+    pub fn principal(&self) -> Option<&Principal> {
+        self.signer.as_ref().map(Signer::principal)
+    }
+
+    /// The binding of the object's namespace.
+    pub fn binding(&self) -> &str {
+        &self.binding
+    }
+
+    /// Sends a request to the object.
+    pub async fn send(&self, request: Value) -> Result<Value, CrateOrObjectError<DynError>> {
+        self.send_envelope(RequestTransport::Request { request })
+            .await?
+            .into_response()
+    }
+
+    /// Initializes the object.
+    pub async fn init(&self, init: Value) -> Result<Result<(), DynError>, crate::Error> {
+        self.send_envelope(RequestTransport::Init { init })
+            .await?
+            .into_initialized()
+    }
+
+    /// Initializes the object and sends it a request.
+    pub async fn init_and_send(
+        &self,
+        init: Value,
+        request: Value,
+    ) -> Result<Value, CrateOrObjectError<DynError>> {
+        self.send_envelope(RequestTransport::InitWithRequest { init, request })
+            .await?
+            .into_response()
+    }
+
+    /// Sends an envelope to the object and returns the response envelope as
+    /// it was received.
+    pub async fn send_envelope(
+        &self,
+        envelope: RequestTransport<Value, Value>,
+    ) -> Result<DynResponse, crate::Error> {
+        let version = EnvelopeVersion {
+            protocol: PROTOCOL_VERSION,
+            api_version: self.api_version,
+        };
+        let principal = self
+            .signer
+            .as_ref()
+            .map(|signer| {
+                let digest = codec::request_digest(version, &envelope)?;
+                signer.sign(
+                    &self.binding,
+                    &signed_object(self.transport.object())?,
+                    &digest,
+                )
+            })
+            .transpose()?;
+        let body = codec::encode_signed_envelope(version, principal.as_ref(), &envelope)?;
+        let body = self.transport.deliver(&self.binding, body).await?;
+
+        codec::decode_envelope(&body)
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{DoProxy, Proxy};
+use crate::{
+    options::{id_transport, named_transport, stub_transport, unique_transport},
+    DoProxy, DynProxy, FanOut, ObjOptions, Proxy, ServiceTransport, ShardedProxy,
+};
 
 /// The [`EnvExt`] trait makes it easy to create proxies from a [`worker::Env`].
 ///
@@ -45,6 +48,105 @@ pub trait EnvExt {
     fn unique_obj<Obj>(&self) -> Result<Proxy<Obj>, worker::Error>
     where
         Obj: DoProxy;
+
+    /// Get a proxy to a durable object with the given name, created according
+    /// to `options`. Unset options fall back to [`DoProxy::OBJ_OPTIONS`].
+    ///
+    /// ```ignore
+    /// let options = ObjOptions::new().location_hint(LocationHint::Weur);
+    /// env.obj_with::<Inserter>("inserter_for_fisher", options)?;
+    /// ```
+    fn obj_with<Obj>(&self, name: &str, options: ObjOptions) -> Result<Proxy<Obj>, worker::Error>
+    where
+        Obj: DoProxy;
+
+    /// Get a proxy to a durable object with the given name in the namespace
+    /// bound to `binding` instead of the one returned by
+    /// [`DoProxy::resolve_binding`]. This is useful when the same object is
+    /// deployed under multiple bindings.
+    ///
+    /// ```ignore
+    /// env.obj_in_binding::<Inserter>("STAGING_INSERTER_OBJECT", "inserter_for_fisher")?;
+    /// ```
+    fn obj_in_binding<Obj>(&self, binding: &str, name: &str) -> Result<Proxy<Obj>, worker::Error>
+    where
+        Obj: DoProxy;
+
+    /// Get a proxy to a durable object with the given name that is owned by
+    /// another worker. Requests are sent through the service binding `service`
+    /// to that worker, which must route them with a [`crate::ObjectRouter`].
+    ///
+    /// ```ignore
+    /// env.obj_via_service::<Inserter>("STORAGE_WORKER", "inserter_for_fisher")?;
+    /// ```
+    fn obj_via_service<Obj>(
+        &self,
+        service: &str,
+        name: &str,
+    ) -> Result<Proxy<Obj, ServiceTransport>, worker::Error>
+    where
+        Obj: DoProxy;
+
+    /// Get a proxy to a durable object with the given hex ID, using `options`.
+    /// Unset options fall back to [`DoProxy::OBJ_OPTIONS`].
+    fn obj_from_id_with<Obj>(
+        &self,
+        id: &str,
+        options: ObjOptions,
+    ) -> Result<Proxy<Obj>, worker::Error>
+    where
+        Obj: DoProxy;
+
+    /// Get a unique proxy to a durable object created according to `options`.
+    /// Unset options fall back to [`DoProxy::OBJ_OPTIONS`].
+    ///
+    /// ```ignore
+    /// let options = ObjOptions::new().jurisdiction(Jurisdiction::Eu);
+    /// env.unique_obj_with::<Inserter>(options)?;
+    /// ```
+    fn unique_obj_with<Obj>(&self, options: ObjOptions) -> Result<Proxy<Obj>, worker::Error>
+    where
+        Obj: DoProxy;
+
+    /// Get a proxy that spreads keys over `shard_count` durable objects named
+    /// `{prefix}-0` through `{prefix}-{shard_count - 1}`.
+    ///
+    /// ```ignore
+    /// env.sharded::<Counter>("page_views", 16)?.get("/index.html")?;
+    /// ```
+    ///
+    /// Fails if `shard_count` is zero.
+    fn sharded<Obj>(
+        &self,
+        prefix: &str,
+        shard_count: u32,
+    ) -> Result<ShardedProxy<Obj>, worker::Error>
+    where
+        Obj: DoProxy;
+
+    /// Get an untyped proxy to the durable object with the given name in the
+    /// namespace bound to `binding`. See [`DynProxy`].
+    ///
+    /// ```ignore
+    /// env.dyn_obj("INSERTER_OBJECT", "inserter_for_fisher")?;
+    /// ```
+    fn dyn_obj(&self, binding: &str, name: &str) -> Result<DynProxy, worker::Error>;
+
+    /// Send a request to every durable object in `names`. The request for each
+    /// object is built by calling `request_factory` with the object's name.
+    ///
+    /// The returned [`FanOut`] can be configured before it is awaited, see its
+    /// documentation for details.
+    ///
+    /// ```ignore
+    /// let report = env.fan_out::<Inserter, _, _>(names, |_| InserterRequest::Count).await;
+    /// ```
+    fn fan_out<Obj, I, F>(&self, names: I, request_factory: F) -> FanOut<'_, Obj, F>
+    where
+        Obj: DoProxy,
+        I: IntoIterator,
+        I::Item: Into<String>,
+        F: FnMut(&str) -> Obj::Request;
 }
 
 impl EnvExt for worker::Env {
@@ -52,32 +154,122 @@ impl EnvExt for worker::Env {
     where
         Obj: DoProxy,
     {
-        let binding = self.durable_object(Obj::BINDING)?;
-        let obj = binding.id_from_name(name)?;
-        let stub = obj.get_stub()?;
-
-        Ok(Proxy::new(stub))
+        self.obj_with(name, ObjOptions::new())
     }
 
     fn obj_from_id<Obj>(&self, id: &str) -> Result<Proxy<Obj>, worker::Error>
     where
         Obj: DoProxy,
     {
-        let binding = self.durable_object(Obj::BINDING)?;
-        let obj = binding.id_from_string(id)?;
-        let stub = obj.get_stub()?;
-
-        Ok(Proxy::new(stub))
+        self.obj_from_id_with(id, ObjOptions::new())
     }
 
     fn unique_obj<Obj>(&self) -> Result<Proxy<Obj>, worker::Error>
     where
         Obj: DoProxy,
     {
-        let binding = self.durable_object(Obj::BINDING)?;
-        let obj = binding.unique_id()?;
-        let stub = obj.get_stub()?;
+        self.unique_obj_with(ObjOptions::new())
+    }
+
+    fn obj_with<Obj>(&self, name: &str, options: ObjOptions) -> Result<Proxy<Obj>, worker::Error>
+    where
+        Obj: DoProxy,
+    {
+        named_proxy(self, Obj::resolve_binding(self), name, options)
+    }
 
-        Ok(Proxy::new(stub))
+    fn obj_in_binding<Obj>(&self, binding: &str, name: &str) -> Result<Proxy<Obj>, worker::Error>
+    where
+        Obj: DoProxy,
+    {
+        named_proxy(self, binding.to_string(), name, ObjOptions::new())
     }
+
+    fn obj_via_service<Obj>(
+        &self,
+        service: &str,
+        name: &str,
+    ) -> Result<Proxy<Obj, ServiceTransport>, worker::Error>
+    where
+        Obj: DoProxy,
+    {
+        let transport = ServiceTransport::new(self.service(service)?, name);
+
+        Ok(Proxy::with_transport(transport, Obj::resolve_binding(self)))
+    }
+
+    fn obj_from_id_with<Obj>(
+        &self,
+        id: &str,
+        options: ObjOptions,
+    ) -> Result<Proxy<Obj>, worker::Error>
+    where
+        Obj: DoProxy,
+    {
+        let binding = Obj::resolve_binding(self);
+        let namespace = self.durable_object(&binding)?;
+        let transport = id_transport(&namespace, id, options.or(Obj::OBJ_OPTIONS))?;
+
+        Ok(Proxy::with_transport(transport, binding))
+    }
+
+    fn unique_obj_with<Obj>(&self, options: ObjOptions) -> Result<Proxy<Obj>, worker::Error>
+    where
+        Obj: DoProxy,
+    {
+        let binding = Obj::resolve_binding(self);
+        let namespace = self.durable_object(&binding)?;
+        let transport = unique_transport(&namespace, options.or(Obj::OBJ_OPTIONS))?;
+
+        Ok(Proxy::with_transport(transport, binding))
+    }
+
+    fn sharded<Obj>(
+        &self,
+        prefix: &str,
+        shard_count: u32,
+    ) -> Result<ShardedProxy<Obj>, worker::Error>
+    where
+        Obj: DoProxy,
+    {
+        let binding = Obj::resolve_binding(self);
+        let namespace = self.durable_object(&binding)?;
+
+        ShardedProxy::new(namespace, binding, prefix, shard_count)
+    }
+
+    fn dyn_obj(&self, binding: &str, name: &str) -> Result<DynProxy, worker::Error> {
+        let transport = stub_transport(&self.durable_object(binding)?.id_from_name(name)?)?;
+
+        Ok(DynProxy::with_transport(transport, binding))
+    }
+
+    fn fan_out<Obj, I, F>(&self, names: I, request_factory: F) -> FanOut<'_, Obj, F>
+    where
+        Obj: DoProxy,
+        I: IntoIterator,
+        I::Item: Into<String>,
+        F: FnMut(&str) -> Obj::Request,
+    {
+        FanOut::new(
+            self,
+            names.into_iter().map(Into::into).collect(),
+            request_factory,
+        )
+    }
+}
+
+fn named_proxy<Obj>(
+    env: &worker::Env,
+    binding: String,
+    name: &str,
+    options: ObjOptions,
+) -> Result<Proxy<Obj>, worker::Error>
+where
+    Obj: DoProxy,
+{
+    let namespace = env.durable_object(&binding)?;
+    let transport = named_transport(&namespace, name, options.or(Obj::OBJ_OPTIONS))?;
+
+    Ok(Proxy::with_transport(transport, binding))
 }
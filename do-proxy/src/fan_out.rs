@@ -0,0 +1,212 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::{Future, IntoFuture},
+    marker::PhantomData,
+    pin::Pin,
+    time::Duration,
+};
+
+use futures::{
+    future::{self, Either},
+    stream::{self, Stream, StreamExt},
+};
+use worker::Env;
+
+use crate::{CrateOrObjectError, DoProxy, EnvExt, ObjectApi};
+
+/// The result of sending a request to a single object of a fan-out.
+pub type FanOutResult<O> =
+    Result<<O as ObjectApi>::Response, CrateOrObjectError<<O as ObjectApi>::Error>>;
+
+/// A builder that sends a request to many objects of the same type by name.
+///
+/// Create fan-outs using [`crate::EnvExt::fan_out`]. Awaiting the builder sends
+/// all requests and gathers them into a [`FanOutReport`]. Use
+/// [`FanOut::stream`] to handle the responses as they arrive instead.
+///
+/// Names are deduplicated, so every object is sent the request once and has a
+/// single entry in the report, no matter how often it's named.
+///
+/// # Example
+///
+/// ```ignore
+/// let report = env
+///     .fan_out::<Inserter, _, _>(names, |_name| InserterRequest::Get { key: key.clone() })
+///     .concurrency(32)
+///     .timeout(Duration::from_secs(2))
+///     .await;
+///
+/// for (name, error) in &report.failures {
+///     console_log!("{name} failed: {error}");
+/// }
+/// ```
+pub struct FanOut<'e, O, F> {
+    env: &'e Env,
+    names: Vec<String>,
+    request_factory: F,
+    concurrency: usize,
+    timeout: Option<Duration>,
+    _phantom: PhantomData<O>,
+}
+
+impl<'e, O: DoProxy, F> FanOut<'e, O, F> {
+    /// The number of requests sent at the same time if
+    /// [`FanOut::concurrency`] isn't called.
+    pub const DEFAULT_CONCURRENCY: usize = 16;
+
+    pub(crate) fn new(env: &'e Env, names: Vec<String>, request_factory: F) -> Self {
+        Self {
+            env,
+            names: dedup_names(names),
+            request_factory,
+            concurrency: Self::DEFAULT_CONCURRENCY,
+            timeout: None,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Sets the maximum number of requests that are in flight at the same
+    /// time. A limit of `0` is treated as `1`.
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    /// Sets how long each object has to respond. Objects that don't respond in
+    /// time fail with [`crate::Error::DeadlineExceeded`].
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+impl<'e, O, F> FanOut<'e, O, F>
+where
+    O: DoProxy + 'e,
+    F: FnMut(&str) -> O::Request + 'e,
+{
+    /// Sends the requests and yields `(name, result)` pairs in the order the
+    /// objects respond.
+    pub fn stream(self) -> impl Stream<Item = (String, FanOutResult<O>)> + 'e {
+        let Self {
+            env,
+            names,
+            mut request_factory,
+            concurrency,
+            timeout,
+            ..
+        } = self;
+
+        stream::iter(names)
+            .map(move |name| {
+                let request = request_factory(&name);
+                async move {
+                    let result = send_one::<O>(env, &name, request, timeout).await;
+                    (name, result)
+                }
+            })
+            .buffer_unordered(concurrency)
+    }
+
+    async fn run(self) -> FanOutReport<O> {
+        let mut report = FanOutReport {
+            responses: HashMap::with_capacity(self.names.len()),
+            failures: HashMap::new(),
+        };
+
+        let mut results = Box::pin(self.stream());
+        while let Some((name, result)) = results.next().await {
+            match result {
+                Ok(response) => {
+                    report.responses.insert(name, response);
+                }
+                Err(error) => {
+                    report.failures.insert(name, error);
+                }
+            }
+        }
+
+        report
+    }
+}
+
+impl<'e, O, F> IntoFuture for FanOut<'e, O, F>
+where
+    O: DoProxy + 'e,
+    F: FnMut(&str) -> O::Request + 'e,
+{
+    type Output = FanOutReport<O>;
+    type IntoFuture = Pin<Box<dyn Future<Output = Self::Output> + 'e>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move { self.run().await })
+    }
+}
+
+/// The gathered results of a [`FanOut`], keyed by object name.
+pub struct FanOutReport<O: DoProxy> {
+    /// The responses of the objects that handled the request.
+    pub responses: HashMap<String, O::Response>,
+    /// The objects that couldn't be reached, timed out or returned an error.
+    pub failures: HashMap<String, CrateOrObjectError<O::Error>>,
+}
+
+impl<O: DoProxy> FanOutReport<O> {
+    /// Returns `true` if every object responded successfully.
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// Removes repeated names, keeping the first occurrence of each.
+fn dedup_names(names: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::with_capacity(names.len());
+    names
+        .into_iter()
+        .filter(|name| seen.insert(name.clone()))
+        .collect()
+}
+
+async fn send_one<O: DoProxy>(
+    env: &Env,
+    name: &str,
+    request: O::Request,
+    timeout: Option<Duration>,
+) -> FanOutResult<O> {
+    let proxy = env.obj::<O>(name).map_err(crate::Error::from)?;
+    let response = proxy.send(request).into_future();
+
+    match timeout {
+        Some(timeout) => {
+            let deadline = Box::pin(worker::Delay::from(timeout));
+            match future::select(response, deadline).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => Err(crate::Error::DeadlineExceeded.into()),
+            }
+        }
+        None => response.await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn dedups_names_in_order() {
+        assert_eq!(
+            dedup_names(names(&["b", "a", "b", "c", "a"])),
+            names(&["b", "a", "c"])
+        );
+    }
+
+    #[test]
+    fn keeps_unique_names() {
+        assert_eq!(dedup_names(names(&["a", "b"])), names(&["a", "b"]));
+        assert!(dedup_names(Vec::new()).is_empty());
+    }
+}
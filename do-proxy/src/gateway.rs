@@ -0,0 +1,292 @@
+use std::{future::Future, pin::Pin, rc::Rc};
+
+use worker::{Env, Method, Request, Response};
+
+use crate::{DoProxy, EnvExt, IntoHttpError, Keyring, Principal, ProblemDetails};
+
+/// The future returned by a [`Gateway`] authentication hook. Resolves to
+/// `Some(response)` to reject the request with `response`, or `None` to let it
+/// through.
+pub type AuthFuture<'a> = Pin<Box<dyn Future<Output = worker::Result<Option<Response>>> + 'a>>;
+
+type AuthHook = dyn for<'a> Fn(&'a Request, &'a Env) -> AuthFuture<'a>;
+type PrincipalHook = dyn Fn(&Request, &Env) -> worker::Result<Option<Principal>>;
+type RouteFuture<'a> = Pin<Box<dyn Future<Output = worker::Result<Response>> + 'a>>;
+type RouteHandler = dyn for<'a> Fn(Request, &'a Env, String, Option<Caller>) -> RouteFuture<'a>;
+
+/// Signs the principals of routed requests, see [`Gateway::principal`].
+struct Signer {
+    keyring: Keyring,
+    principal: Box<PrincipalHook>,
+}
+
+/// The principal a routed request is sent on behalf of.
+type Caller = (Principal, Rc<Signer>);
+
+/// How a [`Gateway`] route addresses its objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Addressing {
+    Name,
+    Id,
+}
+
+#[derive(Clone)]
+struct Route {
+    prefix: String,
+    /// Passes the rest of the path on as is, see [`Gateway::object_with`].
+    raw_key: bool,
+    handler: Rc<RouteHandler>,
+}
+
+/// Exposes objects over plain HTTP.
+///
+/// Each object type is registered under a route prefix. A `POST` to
+/// `{prefix}/{name}` decodes the body into the object's
+/// [`crate::ObjectApi::Request`], sends it to the object with that name and
+/// responds with the object's response as JSON.
+///
+/// Errors are returned as [`ProblemDetails`]: `404 Not Found` for unknown
+/// routes, `405 Method Not Allowed` for anything but `POST` and `400 Bad
+/// Request` for bodies that can't be decoded. Errors returned by the object
+/// are described by its [`IntoHttpError`] impl, while crate errors stay
+/// opaque.
+///
+/// # Example
+///
+/// ```ignore
+/// #[worker::event(fetch, respond_with_errors)]
+/// pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Response> {
+///     Gateway::new()
+///         .object::<Inserter>("/inserter")
+///         .object_by_id::<Counter>("/counter")
+///         .authenticate(|req, _env| {
+///             Box::pin(async move {
+///                 match req.headers().get("authorization")? {
+///                     Some(_) => Ok(None),
+///                     None => Response::error("unauthorized", 401).map(Some),
+///                 }
+///             })
+///         })
+///         .handle(req, &env)
+///         .await
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct Gateway {
+    routes: Vec<Route>,
+    authenticate: Option<Rc<AuthHook>>,
+    signer: Option<Rc<Signer>>,
+}
+
+impl Gateway {
+    /// Creates a gateway without any routes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Routes `{prefix}/{name}` to the object of type `O` with the given name.
+    pub fn object<O>(self, prefix: &str) -> Self
+    where
+        O: DoProxy + 'static,
+        O::Error: IntoHttpError,
+    {
+        self.route::<O>(prefix, Addressing::Name, false, str::to_string)
+    }
+
+    /// Routes `{prefix}/{key}` to the object of type `O` named `name(key)`.
+    /// Useful to keep the names of objects that were created before they were
+    /// exposed through a gateway.
+    ///
+    /// Unlike [`Gateway::object`], `key` is the rest of the path as is: it
+    /// keeps trailing slashes and may be empty.
+    ///
+    /// ```ignore
+    /// .object_with::<Inserter>("", |key| format!("/{key}"))
+    /// ```
+    pub fn object_with<O>(self, prefix: &str, name: impl Fn(&str) -> String + 'static) -> Self
+    where
+        O: DoProxy + 'static,
+        O::Error: IntoHttpError,
+    {
+        self.route::<O>(prefix, Addressing::Name, true, name)
+    }
+
+    /// Routes `{prefix}/{id}` to the object of type `O` with the given hex ID.
+    pub fn object_by_id<O>(self, prefix: &str) -> Self
+    where
+        O: DoProxy + 'static,
+        O::Error: IntoHttpError,
+    {
+        self.route::<O>(prefix, Addressing::Id, false, str::to_string)
+    }
+
+    /// Sets a hook that runs before every routed request. See [`AuthFuture`].
+    pub fn authenticate<F>(mut self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a Request, &'a Env) -> AuthFuture<'a> + 'static,
+    {
+        self.authenticate = Some(Rc::new(hook));
+        self
+    }
+
+    /// Sends routed requests on behalf of the principal returned by `hook`,
+    /// signed with `keyring`, see [`DoProxy::keyring`]. Requests for which
+    /// `hook` returns `None` are sent without a principal. Runs after the
+    /// [`Gateway::authenticate`] hook.
+    ///
+    /// ```ignore
+    /// .principal(Keyring::from_env(&env, "DO_PROXY_KEYS")?, |req, _env| {
+    ///     Ok(req.headers().get("x-user-id")?.map(Principal::new))
+    /// })
+    /// ```
+    pub fn principal<F>(mut self, keyring: Keyring, hook: F) -> Self
+    where
+        F: Fn(&Request, &Env) -> worker::Result<Option<Principal>> + 'static,
+    {
+        self.signer = Some(Rc::new(Signer {
+            keyring,
+            principal: Box::new(hook),
+        }));
+        self
+    }
+
+    /// Handles `req`, see [`Gateway`].
+    pub async fn handle(&self, req: Request, env: &Env) -> worker::Result<Response> {
+        let path = req.path();
+        let Some((route, key)) = self
+            .routes
+            .iter()
+            .find_map(|route| Some((route, object_key(&path, route)?)))
+        else {
+            return ProblemDetails::new(404, "no_route", format!("no route for `{path}`"))
+                .into_response();
+        };
+
+        if req.method() != Method::Post {
+            return ProblemDetails::new(405, "method_not_allowed", "expected a POST request")
+                .into_response();
+        }
+
+        if let Some(authenticate) = &self.authenticate {
+            if let Some(response) = authenticate(&req, env).await? {
+                return Ok(response);
+            }
+        }
+
+        let caller = match &self.signer {
+            Some(signer) => {
+                (signer.principal)(&req, env)?.map(|principal| (principal, signer.clone()))
+            }
+            None => None,
+        };
+
+        (route.handler)(req, env, key.to_string(), caller).await
+    }
+
+    fn route<O>(
+        mut self,
+        prefix: &str,
+        addressing: Addressing,
+        raw_key: bool,
+        key: impl Fn(&str) -> String + 'static,
+    ) -> Self
+    where
+        O: DoProxy + 'static,
+        O::Error: IntoHttpError,
+    {
+        let handler: Rc<RouteHandler> = Rc::new(move |req, env, path_key, caller| {
+            Box::pin(send_to_object::<O>(
+                req,
+                env,
+                key(&path_key),
+                addressing,
+                caller,
+            ))
+        });
+
+        self.routes.push(Route {
+            prefix: prefix.trim_end_matches('/').to_string(),
+            raw_key,
+            handler,
+        });
+        self
+    }
+}
+
+/// Returns the object name or ID in `path` if it starts with the prefix of
+/// `route`.
+fn object_key<'p>(path: &'p str, route: &Route) -> Option<&'p str> {
+    let key = path.strip_prefix(&route.prefix)?.strip_prefix('/')?;
+    if route.raw_key {
+        return Some(key);
+    }
+
+    let key = key.trim_end_matches('/');
+    (!key.is_empty()).then_some(key)
+}
+
+async fn send_to_object<O>(
+    mut req: Request,
+    env: &Env,
+    key: String,
+    addressing: Addressing,
+    caller: Option<Caller>,
+) -> worker::Result<Response>
+where
+    O: DoProxy,
+    O::Error: IntoHttpError,
+{
+    let request: O::Request = match req.json().await {
+        Ok(request) => request,
+        Err(error) => {
+            return ProblemDetails::new(400, "invalid_request", error.to_string()).into_response()
+        }
+    };
+
+    let proxy = match addressing {
+        Addressing::Name => env.obj::<O>(&key)?,
+        Addressing::Id => env.obj_from_id::<O>(&key)?,
+    };
+    let proxy = match caller {
+        Some((principal, signer)) => proxy.with_principal(principal, &signer.keyring)?,
+        None => proxy,
+    };
+
+    match proxy.send(request).await {
+        Ok(response) => Response::from_json(&response),
+        Err(error) => error.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(prefix: &str, raw_key: bool) -> Route {
+        Route {
+            prefix: prefix.to_string(),
+            raw_key,
+            handler: Rc::new(|_, _, _, _| Box::pin(async { Response::empty() })),
+        }
+    }
+
+    #[test]
+    fn keys_are_trimmed() {
+        let route = route("/inserter", false);
+
+        assert_eq!(object_key("/inserter/foo", &route), Some("foo"));
+        assert_eq!(object_key("/inserter/foo/", &route), Some("foo"));
+        assert_eq!(object_key("/inserter/", &route), None);
+        assert_eq!(object_key("/inserter", &route), None);
+        assert_eq!(object_key("/inserterfoo", &route), None);
+    }
+
+    #[test]
+    fn raw_keys_are_kept_as_is() {
+        let route = route("", true);
+
+        assert_eq!(object_key("/foo", &route), Some("foo"));
+        assert_eq!(object_key("/foo/", &route), Some("foo/"));
+        assert_eq!(object_key("/", &route), Some(""));
+    }
+}
@@ -0,0 +1,167 @@
+//! Glue between the object generated by [`crate::do_proxy!`] and [`DoProxy`].
+use futures::lock::Mutex;
+use worker::{Method, Request, Response};
+
+use crate::{
+    codec,
+    proxy_trait::{authenticate, handle_envelope, read_body},
+    transport::{RequestTransport, ResponseTransport, ENVELOPE_HEADER, INIT_HEADER},
+    Ctx, DoProxy, Encoding, ProblemDetails,
+};
+
+pub use crate::socket::{accept, is_upgrade};
+
+/// Returns `true` if `req` carries a do-proxy request envelope. Every request
+/// to an object that speaks JSON-RPC does, see [`Encoding::JsonRpc`].
+///
+/// Callers mark envelopes with the [`ENVELOPE_HEADER`], but callers that
+/// predate it don't, so `POST` requests without it are recognized by their
+/// body instead.
+pub async fn is_envelope<O: DoProxy>(req: &Request) -> bool {
+    if O::ENCODING == Encoding::JsonRpc || matches!(req.headers().get(ENVELOPE_HEADER), Ok(Some(_)))
+    {
+        return true;
+    }
+
+    if req.method() != Method::Post {
+        return false;
+    }
+
+    match req.clone() {
+        Ok(mut req) => matches!(req.text().await, Ok(body) if codec::is_request_envelope(&body)),
+        Err(_) => false,
+    }
+}
+
+/// Where the object generated by [`crate::do_proxy!`] caches its [`DoProxy`].
+///
+/// Requests, socket frames and alarms lock the slot for as long as they use
+/// the object, so they're handled one at a time and the object is loaded from
+/// storage only once. An object must not wait for a request to itself while
+/// it handles one, as that request would wait for the lock forever.
+pub type ObjectSlot<O> = Mutex<Option<O>>;
+
+/// Runs [`DoProxy::run_request`] against the object cached in `object`.
+pub async fn run_cached<O: DoProxy>(
+    object: &ObjectSlot<O>,
+    ctx: &mut Ctx<'_>,
+    req: Option<Request>,
+) -> worker::Result<Response> {
+    let mut cached = object.lock().await;
+    O::run_request(&mut cached, ctx, req).await
+}
+
+/// Runs [`DoProxy::handle_raw`] on the object cached in `object`. Writes
+/// buffered on `ctx` are flushed if it returns a response, see [`Ctx::put`].
+pub async fn run_raw<O: DoProxy>(
+    object: &ObjectSlot<O>,
+    ctx: &mut Ctx<'_>,
+    req: Request,
+) -> worker::Result<Response> {
+    let mut cached = object.lock().await;
+    let proxy = cached_or_load(&mut cached, ctx)
+        .await
+        .map_err(|e| e.to_string())?;
+    let response = proxy.handle_raw(ctx, req).await;
+    if response.is_err() {
+        ctx.discard_writes();
+    } else if let Err(error) = ctx.flush_writes().await {
+        // The cached object may not match storage anymore.
+        *cached = None;
+        return Err(error.into());
+    }
+
+    response
+}
+
+/// Handles a plain JSON request, see [`DoProxy::PLAIN_JSON`].
+pub async fn run_plain<O: DoProxy>(
+    object: &ObjectSlot<O>,
+    ctx: &mut Ctx<'_>,
+    mut req: Request,
+) -> worker::Result<Response> {
+    if let Err(error) = authenticate::<O>(ctx, None) {
+        return plain_failure(error);
+    }
+
+    let init = match plain_init::<O>(&req) {
+        Ok(init) => init,
+        Err(error) => return plain_failure(error),
+    };
+
+    let body = match read_body::<O>(&mut req).await {
+        Ok(body) => body,
+        Err(error) => return plain_failure(error),
+    };
+    let envelope = match init {
+        Some(init) if body.trim().is_empty() => RequestTransport::Init { init },
+        init => {
+            let request = match serde_json::from_str(&body) {
+                Ok(request) => request,
+                Err(err) => return plain_failure(crate::Error::decode(&err, &body)),
+            };
+
+            match init {
+                Some(init) => RequestTransport::InitWithRequest { init, request },
+                None => RequestTransport::Request { request },
+            }
+        }
+    };
+
+    let response = handle_envelope(&mut *object.lock().await, ctx, Some(envelope)).await;
+
+    match response {
+        ResponseTransport::Response { response } => Response::from_json(&response),
+        ResponseTransport::Error { error } => {
+            Ok(Response::from_json(&error)?.with_status(O::plain_error_status(&error)))
+        }
+        ResponseTransport::Initialized => Ok(Response::empty()?.with_status(204)),
+        ResponseTransport::Failure { error } => plain_failure(error),
+    }
+}
+
+/// Reads the init data of a plain JSON request from the [`INIT_HEADER`] or
+/// the `init` query parameter.
+fn plain_init<O: DoProxy>(req: &Request) -> Result<Option<O::Init>, crate::Error> {
+    let init = match req.headers().get(INIT_HEADER)? {
+        Some(init) => init,
+        None => match req.url()?.query_pairs().find(|(name, _)| name == "init") {
+            Some((_, init)) => init.into_owned(),
+            None => return Ok(None),
+        },
+    };
+
+    serde_json::from_str(&init).map_err(|err| crate::Error::decode(&err, &init))
+}
+
+/// Failures caused by the request itself are described to the client, all
+/// others stay opaque.
+fn plain_failure(error: crate::Error) -> worker::Result<Response> {
+    let problem = match &error {
+        crate::Error::Decode { .. }
+        | crate::Error::ProtocolMismatch { .. }
+        | crate::Error::UnknownCommand { .. } => {
+            ProblemDetails::new(400, error.code(), error.to_string())
+        }
+        crate::Error::ObjectNotInitialized { .. } => {
+            ProblemDetails::new(404, error.code(), "the object has not been initialized")
+        }
+        _ => ProblemDetails::from_error(&error),
+    };
+
+    problem.into_response()
+}
+
+/// Returns the object cached in `cached`, loading it from storage if it isn't
+/// cached yet.
+pub(crate) async fn cached_or_load<'c, O: DoProxy>(
+    cached: &'c mut Option<O>,
+    ctx: &mut Ctx<'_>,
+) -> Result<&'c mut O, O::Error> {
+    let proxy = match cached.take() {
+        Some(proxy) => proxy,
+        None => O::load_from_storage(ctx).await?,
+    };
+
+    Ok(cached.insert(proxy))
+}
@@ -12,25 +12,64 @@
 //! do-proxy provides a core trait [`DoProxy`] that abstracts over ser/de request
 //! response code, object initalization and loading, and Error handling glue code.
 //!
+//! The request, response and error types of an object are declared with
+//! [`ObjectApi`], which lives in the runtime-independent `do-proxy-core` crate
+//! together with the request envelopes and error types. Crates that only share
+//! an object's API with native services can depend on `do-proxy-core` alone.
+//!
 //! After a struct implements [`DoProxy`], the macro [`do_proxy!`] creates the
 //! [workers-rs](https://github.com/cloudflare/workers-rs)' `#[DurableObject]`
 //! struct which ends up generating the final object.
 //!
 //! See [`DoProxy`] for more details.
+mod dyn_proxy;
 mod env_ext;
-mod error;
+mod fan_out;
+mod gateway;
+#[doc(hidden)]
+pub mod glue;
 mod macros;
+mod options;
 mod proxy;
 mod proxy_trait;
-mod transport;
+mod proxy_transport;
+mod router;
+mod sharded;
+mod socket;
 
 pub use self::{
+    dyn_proxy::{DynError, DynProxy, DynResponse},
     env_ext::EnvExt,
-    error::{CrateOrObjectError, Error},
+    fan_out::{FanOut, FanOutReport, FanOutResult},
+    gateway::{AuthFuture, Gateway},
+    options::{Jurisdiction, LocationHint, ObjOptions},
     proxy::Proxy,
     proxy_trait::{Ctx, DoProxy, ProxiedRequest},
+    proxy_transport::{
+        HttpTransport, Loopback, LoopbackFuture, ServiceTransport, StubTransport, Transport,
+    },
+    router::{forward_envelope, ObjectRouter},
+    sharded::ShardedProxy,
+    socket::{Session, SessionId, SessionMessage, Sessions},
+};
+
+pub use do_proxy_core::{
+    auth::{self, AuditRecord, Keyring, Policy, Principal, Rule},
+    codec, jsonrpc,
+    transport::{self, BINDING_HEADER, ENVELOPE_HEADER, INIT_HEADER, NAME_HEADER},
+    Cause, CrateOrObjectError, Encoding, Error, FieldError, IntoHttpError, ObjectApi,
+    ProblemDetails, Validate, ValidationErrors,
 };
 
+#[cfg(feature = "http-client")]
+pub use do_proxy_core::HttpProxy;
+
+#[cfg(feature = "schemars")]
+pub use do_proxy_core::{compat, schema};
+
+#[cfg(feature = "typescript")]
+pub use do_proxy_core::typescript;
+
 pub use ::async_trait::async_trait;
 pub use ::paste;
 pub use ::worker;
@@ -14,29 +14,50 @@ macro_rules! do_proxy {
 
                 #[worker::durable_object]
                 pub struct $obj_name {
-                    state: State,
-                    env: Env,
-                    proxy: Option<$proxy_name>,
+                    state: std::rc::Rc<State>,
+                    env: std::rc::Rc<Env>,
+                    proxy: std::rc::Rc<$crate::glue::ObjectSlot<$proxy_name>>,
+                    sessions: $crate::Sessions,
                 }
 
                 #[worker::durable_object]
                 impl worker::DurableObject for $obj_name {
                     fn new(state: State, env: Env) -> Self {
                         Self {
-                            state,
-                            env,
-                            proxy: None,
+                            state: std::rc::Rc::new(state),
+                            env: std::rc::Rc::new(env),
+                            proxy: Default::default(),
+                            sessions: Default::default(),
                         }
                     }
 
                     async fn fetch(&mut self, req: worker::Request) -> worker::Result<Response> {
-                        let mut ctx = $crate::Ctx::new(&self.state, &self.env);
-                        $proxy_name::run_request(&mut self.proxy, &mut ctx, Some(req)).await
+                        if $crate::glue::is_upgrade(&req) {
+                            return $crate::glue::accept(
+                                self.proxy.clone(),
+                                self.state.clone(),
+                                self.env.clone(),
+                                self.sessions.clone(),
+                            );
+                        }
+
+                        let mut ctx = $crate::Ctx::new(&self.state, &self.env)
+                            .with_sessions(&self.sessions);
+                        if !$crate::glue::is_envelope::<$proxy_name>(&req).await {
+                            if <$proxy_name as DoProxy>::PLAIN_JSON {
+                                return $crate::glue::run_plain(&self.proxy, &mut ctx, req).await;
+                            }
+
+                            return $crate::glue::run_raw(&self.proxy, &mut ctx, req).await;
+                        }
+
+                        $crate::glue::run_cached(&self.proxy, &mut ctx, Some(req)).await
                     }
 
                     async fn alarm(&mut self) -> worker::Result<Response> {
-                        let mut ctx = $crate::Ctx::new(&self.state, &self.env);
-                        $proxy_name::run_request(&mut self.proxy, &mut ctx, None).await
+                        let mut ctx = $crate::Ctx::new(&self.state, &self.env)
+                            .with_sessions(&self.sessions);
+                        $crate::glue::run_cached(&self.proxy, &mut ctx, None).await
                     }
                 }
             }
@@ -0,0 +1,243 @@
+use worker::{
+    js_sys::{Function, Object, Reflect},
+    wasm_bindgen::{closure::Closure, JsCast, JsValue},
+    ObjectId, ObjectNamespace,
+};
+
+use crate::{auth::ObjectRef, StubTransport};
+
+/// Options that control where a durable object is created.
+///
+/// Every [`crate::DoProxy`] type has default options, see
+/// [`crate::DoProxy::OBJ_OPTIONS`]. Options passed to methods like
+/// [`crate::EnvExt::obj_with`] take precedence over the type's defaults.
+///
+/// # Example
+///
+/// ```ignore
+/// let options = ObjOptions::new().location_hint(LocationHint::Weur);
+/// let proxy = env.obj_with::<Inserter>("inserter_for_fisher", options)?;
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ObjOptions {
+    /// Hints where the object should be created when it's first accessed.
+    /// Has no effect on objects that already exist.
+    pub location_hint: Option<LocationHint>,
+    /// Restricts the object to a jurisdiction. The object and its storage will
+    /// only ever be located in that jurisdiction.
+    ///
+    /// Named objects are looked up in the namespace of the jurisdiction, so the
+    /// same name refers to different objects in different jurisdictions.
+    pub jurisdiction: Option<Jurisdiction>,
+}
+
+impl ObjOptions {
+    /// Options that don't restrict where an object is created.
+    pub const fn new() -> Self {
+        Self {
+            location_hint: None,
+            jurisdiction: None,
+        }
+    }
+
+    /// Sets the location hint.
+    pub const fn location_hint(mut self, hint: LocationHint) -> Self {
+        self.location_hint = Some(hint);
+        self
+    }
+
+    /// Sets the jurisdiction.
+    pub const fn jurisdiction(mut self, jurisdiction: Jurisdiction) -> Self {
+        self.jurisdiction = Some(jurisdiction);
+        self
+    }
+
+    /// Returns these options with every unset field taken from `defaults`.
+    pub fn or(self, defaults: ObjOptions) -> Self {
+        Self {
+            location_hint: self.location_hint.or(defaults.location_hint),
+            jurisdiction: self.jurisdiction.or(defaults.jurisdiction),
+        }
+    }
+}
+
+/// A region a durable object can be hinted to be created in.
+///
+/// See <https://developers.cloudflare.com/durable-objects/reference/data-location/>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LocationHint {
+    /// Western North America.
+    Wnam,
+    /// Eastern North America.
+    Enam,
+    /// South America.
+    Sam,
+    /// Western Europe.
+    Weur,
+    /// Eastern Europe.
+    Eeur,
+    /// Asia-Pacific.
+    Apac,
+    /// Oceania.
+    Oc,
+    /// Africa.
+    Afr,
+    /// Middle East.
+    Me,
+}
+
+impl LocationHint {
+    /// The name of the region used by the runtime, for example `"weur"`.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            LocationHint::Wnam => "wnam",
+            LocationHint::Enam => "enam",
+            LocationHint::Sam => "sam",
+            LocationHint::Weur => "weur",
+            LocationHint::Eeur => "eeur",
+            LocationHint::Apac => "apac",
+            LocationHint::Oc => "oc",
+            LocationHint::Afr => "afr",
+            LocationHint::Me => "me",
+        }
+    }
+}
+
+/// A jurisdiction a durable object can be restricted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Jurisdiction {
+    /// The European Union.
+    Eu,
+    /// FedRAMP-compliant data centers.
+    FedRamp,
+}
+
+impl Jurisdiction {
+    /// The name of the jurisdiction used by the runtime, for example `"eu"`.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Jurisdiction::Eu => "eu",
+            Jurisdiction::FedRamp => "fedramp",
+        }
+    }
+}
+
+/// Get a transport to the object with the given name.
+pub(crate) fn named_transport(
+    namespace: &ObjectNamespace,
+    name: &str,
+    options: ObjOptions,
+) -> Result<StubTransport, worker::Error> {
+    in_namespace(namespace, options, |namespace| {
+        stub_transport(&namespace.id_from_name(name)?)
+    })
+}
+
+/// Get a transport to the object with the given hex ID.
+pub(crate) fn id_transport(
+    namespace: &ObjectNamespace,
+    id: &str,
+    options: ObjOptions,
+) -> Result<StubTransport, worker::Error> {
+    in_namespace(namespace, options, |namespace| {
+        stub_transport(&namespace.id_from_string(id)?)
+    })
+}
+
+/// Get a transport to a new unique object.
+pub(crate) fn unique_transport(
+    namespace: &ObjectNamespace,
+    options: ObjOptions,
+) -> Result<StubTransport, worker::Error> {
+    in_namespace(namespace, options, |namespace| {
+        stub_transport(&namespace.unique_id()?)
+    })
+}
+
+/// Get the hex ID of the object with the given name.
+pub(crate) fn named_id(
+    namespace: &ObjectNamespace,
+    name: &str,
+    options: ObjOptions,
+) -> Result<String, worker::Error> {
+    in_namespace(namespace, options, |namespace| {
+        Ok(namespace.id_from_name(name)?.to_string())
+    })
+}
+
+/// Get a transport to the object `id`, which principals are signed for by ID.
+pub(crate) fn stub_transport(id: &ObjectId<'_>) -> Result<StubTransport, worker::Error> {
+    Ok(StubTransport::new(id.get_stub()?).with_object(ObjectRef::Id(id.to_string())))
+}
+
+/// Calls `f` with the namespace objects are created in according to
+/// `options`, i.e. the jurisdiction's namespace if there is one, with stubs
+/// carrying the location hint if there is one.
+fn in_namespace<T>(
+    namespace: &ObjectNamespace,
+    options: ObjOptions,
+    f: impl FnOnce(&ObjectNamespace) -> Result<T, worker::Error>,
+) -> Result<T, worker::Error> {
+    let scoped;
+    let namespace = match options.jurisdiction {
+        Some(jurisdiction) => {
+            scoped = jurisdiction_namespace(namespace, jurisdiction)?;
+            &scoped
+        }
+        None => namespace,
+    };
+
+    let hinted;
+    let namespace = match options.location_hint {
+        Some(hint) => {
+            hinted = hinted_namespace(namespace, hint)?;
+            &hinted
+        }
+        None => namespace,
+    };
+
+    f(namespace)
+}
+
+/// Calls `namespace.jurisdiction(..)`, which workers-rs doesn't expose.
+fn jurisdiction_namespace(
+    namespace: &ObjectNamespace,
+    jurisdiction: Jurisdiction,
+) -> Result<ObjectNamespace, worker::Error> {
+    let namespace: &JsValue = namespace.as_ref();
+    let scope: Function = Reflect::get(namespace, &JsValue::from("jurisdiction"))?.dyn_into()?;
+
+    Ok(scope
+        .call1(namespace, &JsValue::from(jurisdiction.as_str()))?
+        .unchecked_into())
+}
+
+/// Wraps `namespace` so that `get(id)` calls `namespace.get(id, { locationHint })`,
+/// which workers-rs doesn't expose. The other methods are bound to `namespace`.
+fn hinted_namespace(
+    namespace: &ObjectNamespace,
+    hint: LocationHint,
+) -> Result<ObjectNamespace, worker::Error> {
+    let namespace: &JsValue = namespace.as_ref();
+    let hinted: Object = Object::create(namespace.unchecked_ref());
+    for method in ["idFromName", "idFromString", "newUniqueId"] {
+        let method = JsValue::from(method);
+        let function: Function = Reflect::get(namespace, &method)?.dyn_into()?;
+        Reflect::set(&hinted, &method, &function.bind0(namespace))?;
+    }
+
+    let get: Function = Reflect::get(namespace, &JsValue::from("get"))?.dyn_into()?;
+    let options: Object = Object::new();
+    Reflect::set(
+        &options,
+        &JsValue::from("locationHint"),
+        &JsValue::from(hint.as_str()),
+    )?;
+    let target = namespace.clone();
+    let get = Closure::<dyn Fn(JsValue) -> Result<JsValue, JsValue>>::new(move |id| {
+        get.call2(&target, &id, &options)
+    });
+    Reflect::set(&hinted, &JsValue::from("get"), &get.into_js_value())?;
+
+    Ok(hinted.unchecked_into())
+}
@@ -4,35 +4,90 @@ use std::{
     pin::Pin,
 };
 
-use worker::Stub;
-
 use crate::{
-    transport::{RequestTransport, ResponseTransport},
-    CrateOrObjectError, DoProxy,
+    auth::{Keyring, ObjectRef, Principal, Signer},
+    codec,
+    transport::RequestTransport,
+    CrateOrObjectError, DoProxy, StubTransport, Transport,
 };
 
-/// A wrapper around a [`worker::Stub`] that provides a builder interface for
+/// A wrapper around a [`Transport`] that provides a builder interface for
 /// sending requests. This is the only way to send requests to a durable object
 /// in a type-safe way.
 ///
-/// Create proxies using the [`crate::EnvExt`] trait.
+/// Create proxies using the [`crate::EnvExt`] trait. Proxies created with
+/// [`crate::EnvExt::obj_via_service`] reach the object through a service
+/// binding to the worker that owns it instead of a stub. Use
+/// [`Proxy::with_transport`] to deliver requests any other way.
 ///
 /// The `Builder` type returned by [`Proxy::send`] and [`Proxy::init`]
 /// implements [`std::future::IntoFuture`]. This means, you must use `.await` to
 /// actually send the request.
-pub struct Proxy<O> {
-    stub: Stub,
+pub struct Proxy<O, T = StubTransport> {
+    transport: T,
+    binding: String,
+    signer: Option<Signer>,
     _phantom: PhantomData<O>,
 }
 
-impl<O: DoProxy> Proxy<O> {
-    pub(crate) fn new(stub: Stub) -> Self {
+impl<O: DoProxy, T: Transport> Proxy<O, T> {
+    /// Creates a proxy that delivers requests for the object bound under
+    /// `binding` through `transport`.
+    pub fn with_transport(transport: T, binding: impl Into<String>) -> Self {
         Self {
-            stub,
+            transport,
+            binding: binding.into(),
+            signer: None,
             _phantom: PhantomData,
         }
     }
 
+    /// Sends every request on behalf of `principal`, signed with `keyring`.
+    /// Each request is signed as it's sent, so the signatures don't expire
+    /// while the proxy is kept around. Signatures are bound to the request and
+    /// to the transport's [`Transport::object`], sending requests fails if the
+    /// transport doesn't know its object. See [`DoProxy::keyring`].
+    ///
+    /// ```ignore
+    /// let keyring = Keyring::from_env(&env, "DO_PROXY_KEYS")?;
+    /// let proxy = env.obj::<Person>("bob")?.with_principal(Principal::new(user_id), &keyring)?;
+    /// ```
+    pub fn with_principal(
+        mut self,
+        principal: Principal,
+        keyring: &Keyring,
+    ) -> Result<Self, crate::Error> {
+        self.signer = Some(Signer::new(principal, keyring)?);
+        Ok(self)
+    }
+
+    /// The principal requests are sent on behalf of, see
+    /// [`Proxy::with_principal`].
+    pub fn principal(&self) -> Option<&Principal> {
+        self.signer.as_ref().map(Signer::principal)
+    }
+
+    pub(crate) fn signer(&self) -> Option<&Signer> {
+        self.signer.as_ref()
+    }
+
+    /// The binding of the durable object namespace this proxy sends requests
+    /// to. See [`DoProxy::resolve_binding`].
+    pub fn binding(&self) -> &str {
+        &self.binding
+    }
+
+    /// The transport requests are delivered with.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Sends an already serialized request envelope to the object and returns
+    /// its response envelope as-is.
+    pub(crate) async fn forward(&self, body: String) -> Result<String, crate::Error> {
+        self.transport.deliver(&self.binding, body).await
+    }
+
     /// Send a request to the durable object. You must await this future to
     /// # Example
     ///
@@ -41,7 +96,7 @@ impl<O: DoProxy> Proxy<O> {
     /// ```
     #[must_use = "you must await this future to send the request"]
     pub fn send(&self, request: O::Request) -> Builder<'_, O, Send> {
-        Builder::new(&self.stub).send(request)
+        Builder::new(self).send(request)
     }
 
     /// Send a request to the durable object. You can immediately `await` the
@@ -60,12 +115,14 @@ impl<O: DoProxy> Proxy<O> {
     /// ```
     #[must_use = "you must await this future to send the request"]
     pub fn init(&self, init: O::Init) -> Builder<'_, O, WithInit> {
-        Builder::new(&self.stub).init(init)
+        Builder::new(self).init(init)
     }
 }
 
 pub struct Builder<'s, O: DoProxy, State> {
-    stub: &'s Stub,
+    transport: &'s dyn Transport,
+    binding: &'s str,
+    signer: Option<&'s Signer>,
     request: RequestTransport<O::Init, O::Request>,
     _phantom: PhantomData<State>,
 }
@@ -75,9 +132,11 @@ pub struct WithInit;
 pub struct Send;
 
 impl<'s, O: DoProxy> Builder<'s, O, New> {
-    pub fn new(stub: &'s Stub) -> Self {
+    pub(crate) fn new<T: Transport>(proxy: &'s Proxy<O, T>) -> Self {
         Self {
-            stub,
+            transport: &proxy.transport,
+            binding: &proxy.binding,
+            signer: proxy.signer.as_ref(),
             request: RequestTransport::Empty,
             _phantom: PhantomData,
         }
@@ -87,7 +146,9 @@ impl<'s, O: DoProxy> Builder<'s, O, New> {
 impl<'s, O: DoProxy> Builder<'s, O, New> {
     pub fn send(self, request: O::Request) -> Builder<'s, O, Send> {
         Builder {
-            stub: self.stub,
+            transport: self.transport,
+            binding: self.binding,
+            signer: self.signer,
             request: RequestTransport::Request { request },
             _phantom: PhantomData,
         }
@@ -95,7 +156,9 @@ impl<'s, O: DoProxy> Builder<'s, O, New> {
 
     pub fn init(self, init: O::Init) -> Builder<'s, O, WithInit> {
         Builder {
-            stub: self.stub,
+            transport: self.transport,
+            binding: self.binding,
+            signer: self.signer,
             request: RequestTransport::Init { init },
             _phantom: PhantomData,
         }
@@ -105,7 +168,9 @@ impl<'s, O: DoProxy> Builder<'s, O, New> {
 impl<'s, O: DoProxy> Builder<'s, O, WithInit> {
     pub fn and_send(mut self, request: O::Request) -> Builder<'s, O, Send> {
         Builder {
-            stub: self.stub,
+            transport: self.transport,
+            binding: self.binding,
+            signer: self.signer,
             request: RequestTransport::InitWithRequest {
                 init: self.request.take_init().unwrap(),
                 request,
@@ -117,27 +182,17 @@ impl<'s, O: DoProxy> Builder<'s, O, WithInit> {
 
 impl<'s, O: DoProxy> Builder<'s, O, Send> {
     async fn run(self) -> Result<O::Response, CrateOrObjectError<O::Error>> {
-        match send_to_stub::<O>(self.stub, self.request).await {
-            Ok(response) => match response {
-                ResponseTransport::Response { response } => Ok(response),
-                ResponseTransport::Error { error } => Err(CrateOrObjectError::Object(error)),
-                ResponseTransport::Initialized => Err(crate::Error::ExpectedObjectResponse.into()),
-            },
-            Err(error) => Err(error.into()),
-        }
+        send_to_object::<O>(self.transport, self.binding, self.signer, self.request)
+            .await?
+            .into_response()
     }
 }
 
 impl<'s, O: DoProxy> Builder<'s, O, WithInit> {
     async fn run(self) -> Result<Result<(), O::Error>, crate::Error> {
-        match send_to_stub::<O>(self.stub, self.request).await {
-            Ok(response) => match response {
-                ResponseTransport::Initialized => Ok(Ok(())),
-                ResponseTransport::Response { .. } => Err(crate::Error::ExpectedObjectInitialized),
-                ResponseTransport::Error { error } => Ok(Err(error)),
-            },
-            Err(error) => Err(error),
-        }
+        send_to_object::<O>(self.transport, self.binding, self.signer, self.request)
+            .await?
+            .into_initialized()
     }
 }
 
@@ -159,21 +214,24 @@ impl<'s, O: DoProxy + 's> IntoFuture for Builder<'s, O, WithInit> {
     }
 }
 
-async fn send_to_stub<O: DoProxy>(
-    stub: &Stub,
-    req: RequestTransport<O::Init, O::Request>,
-) -> Result<ResponseTransport<O::Response, O::Error>, crate::Error> {
-    let json = serde_json::to_string(&req)?;
-
-    let mut request_init = worker::RequestInit::new();
-    request_init
-        .with_method(worker::Method::Post)
-        .with_body(Some(json.into()));
-
-    let request =
-        worker::Request::new_with_init(&format!("http://{}/", O::BINDING), &request_init)?;
-    let response: ResponseTransport<O::Response, O::Error> =
-        stub.fetch_with_request(request).await?.json().await?;
+async fn send_to_object<O: DoProxy>(
+    transport: &dyn Transport,
+    binding: &str,
+    signer: Option<&Signer>,
+    req: codec::Request<O>,
+) -> Result<codec::Response<O>, crate::Error> {
+    let principal = signer
+        .map(|signer| signer.sign_request::<O>(&signed_object(transport.object())?, &req))
+        .transpose()?;
+    let json = codec::encode_signed_request::<O>(&req, principal.as_ref())?;
+    let body = transport.deliver(binding, json).await?;
+
+    codec::decode_response::<O>(&body)
+}
 
-    Ok(response)
+/// The object principals are signed for, see [`Transport::object`].
+pub(crate) fn signed_object(object: Option<ObjectRef>) -> Result<ObjectRef, crate::Error> {
+    object.ok_or_else(|| {
+        crate::Error::worker("the transport doesn't know its object, so it can't send principals")
+    })
 }
@@ -1,12 +1,20 @@
 #![allow(unused)]
 
-use std::{error::Error, marker::PhantomData};
+use std::{collections::BTreeMap, error::Error, marker::PhantomData};
 
 use async_trait::async_trait;
 use serde::{de::DeserializeOwned, Serialize};
-use worker::{Env, State, Stub};
+use serde_json::Value;
+use worker::{js_sys::JSON, wasm_bindgen::JsValue, Env, State, Stub};
 
-use crate::transport::{RequestTransport, ResponseTransport};
+use crate::{
+    auth::{AuditRecord, Keyring, ObjectRef, Policy, Principal, SignedPrincipal, INIT_COMMAND},
+    codec, jsonrpc,
+    options::named_id,
+    socket::event_frame,
+    transport::{RequestTransport, ResponseTransport},
+    Encoding, ObjOptions, ObjectApi, SessionId, Sessions, Validate, ValidationErrors,
+};
 
 /// A request sent to an object.
 pub enum ProxiedRequest<R> {
@@ -18,77 +26,146 @@ pub enum ProxiedRequest<R> {
 /// trait for a type you want to make into a durable object and automatically
 /// get many helper methods for interacting with it.
 ///
+/// The types the object accepts and returns are declared by implementing its
+/// supertrait, [`ObjectApi`].
+///
 /// After implementing this trait, use the macro `do_proxy!` to generate the
 /// workers-rs [`worker::DurableObject`] glue code.
 ///
 /// See the crates under `examples/*` for example implementations.
 #[async_trait(?Send)]
-pub trait DoProxy
+pub trait DoProxy: ObjectApi
 where
     Self: Sized,
 {
-    /// The Durable Object's binding. Must be the same as the one written in
-    /// your `wrangler.toml`. For example, `INSERTER_OBJECT`.
-    const BINDING: &'static str;
-
-    /// The initialization data that will be passed to the the object when it is
-    /// first created. This should be used to set data that is expected to
-    /// always be available when the object loads. For example, the first time a
-    /// `Person` object is created, it should be initialized with the person's
-    /// name. This way when `load_from_storage` is called, we can expect a
-    /// stored `name` field to always be present. If it is not present, then the
-    /// object has not yet been initialized and `load_from_storage` should
-    /// error.
+    /// The default options used when getting a proxy to this object, for
+    /// example through [`crate::EnvExt::obj`]. Use this to give every object of
+    /// this type a location hint or jurisdiction.
     ///
     /// # Example
     ///
     /// ```ignore
-    /// struct NewPerson {
-    ///     name: String,
-    ///     birthday: DateTime<Utc>,
-    /// }
+    /// const OBJ_OPTIONS: ObjOptions = ObjOptions::new().location_hint(LocationHint::Weur);
     /// ```
-    type Init: Serialize + DeserializeOwned + 'static;
-    /// The request type that will be sent to the object. This is generally an
-    /// enum of all of the different "commands" that the object can handle.
+    const OBJ_OPTIONS: ObjOptions = ObjOptions::new();
+
+    /// Returns the binding proxies to this object are created with. Every
+    /// [`crate::EnvExt`] method except [`crate::EnvExt::obj_in_binding`] uses
+    /// it.
     ///
-    /// # Example
+    /// By default, the binding can be overridden with an environment variable
+    /// named `{BINDING}_BINDING`. For example, setting `INSERTER_OBJECT_BINDING =
+    /// "STAGING_INSERTER_OBJECT"` in your `wrangler.toml` sends all requests for
+    /// `INSERTER_OBJECT` to `STAGING_INSERTER_OBJECT`. If the variable isn't set,
+    /// [`Self::BINDING`] is used.
+    fn resolve_binding(env: &Env) -> String {
+        env.var(&format!("{}_BINDING", Self::BINDING))
+            .map(|binding| binding.to_string())
+            .unwrap_or_else(|_| Self::BINDING.to_string())
+    }
+
+    /// The keyring the principals of callers are verified with, see
+    /// [`crate::auth`]. If it returns a keyring, every request must carry a
+    /// principal signed with one of its keys, or it's rejected with
+    /// [`crate::Error::Unauthenticated`] before reaching the object. The
+    /// verified principal is available through [`Ctx::principal`].
+    ///
+    /// Principals are only accepted by the object and along with the request
+    /// they were signed for. Principals signed for an object's name are
+    /// resolved with [`DoProxy::OBJ_OPTIONS`].
+    ///
+    /// Plain JSON and JSON-RPC requests can't carry a principal, so they're
+    /// always rejected if the object has a keyring. Requests passed to
+    /// [`DoProxy::handle_raw`] aren't authenticated.
+    ///
+    /// By default, the object has no keyring and accepts every request.
     ///
     /// ```ignore
-    /// enum PersonRequest {
-    ///     GetAge,
-    ///     GetNextBirthday,
-    ///     GetName,
+    /// fn keyring(env: &Env) -> Result<Option<Keyring>, do_proxy::Error> {
+    ///     Keyring::from_env(env, "DO_PROXY_KEYS").map(Some)
     /// }
     /// ```
-    type Request: Serialize + DeserializeOwned + 'static;
-    /// The response type that will be sent back from the object This is generally
-    /// an enum of all of the different "responses" that the object can send.
-    ///
-    /// Note, types like `Option<serde_json::Value>` will work!
+    fn keyring(env: &Env) -> Result<Option<Keyring>, crate::Error> {
+        Ok(None)
+    }
+
+    /// The authorization policy of the object, see [`Policy`]. Used by the
+    /// default [`DoProxy::authorize`] and [`DoProxy::authorize_init`]. By
+    /// default, anyone may send any command.
+    fn policy() -> Policy {
+        Policy::allow_all()
+    }
+
+    /// Decides whether the caller may send `request`, before the object is
+    /// loaded. `principal` is the caller's verified principal, see
+    /// [`DoProxy::keyring`]. Return [`crate::Error::Forbidden`] to reject the
+    /// request.
     ///
-    /// # Example
+    /// By default, the request is checked against [`DoProxy::policy`], see
+    /// [`Policy::check_request`].
+    async fn authorize(
+        ctx: &Ctx,
+        principal: Option<&Principal>,
+        request: &Self::Request,
+    ) -> Result<(), crate::Error> {
+        Self::policy().check_request(principal, request)
+    }
+
+    /// Decides whether the caller may initialize the object, see
+    /// [`DoProxy::authorize`]. By default, [`INIT_COMMAND`] is checked
+    /// against [`DoProxy::policy`].
+    async fn authorize_init(
+        ctx: &Ctx,
+        principal: Option<&Principal>,
+        init: &Self::Init,
+    ) -> Result<(), crate::Error> {
+        Self::policy().check(principal, INIT_COMMAND)
+    }
+
+    /// Called with the outcome of every authorization check, allowed or not,
+    /// e.g. to write an audit log. Implementing it is _optional_.
     ///
     /// ```ignore
-    /// enum PersonResponse {
-    ///     Age(usize),
-    ///     Birthday(chrono::DateTime<chrono::Utc>),
-    ///     GetName(String),
+    /// async fn audit(ctx: &Ctx, record: AuditRecord<'_>) {
+    ///     worker::console_log!("{}", serde_json::to_string(&record).unwrap_or_default());
     /// }
     /// ```
-    type Response: Serialize + DeserializeOwned + 'static;
+    async fn audit(ctx: &Ctx, record: AuditRecord<'_>) {}
 
-    /// The error type that will be returned from the object. This lets users
-    /// cleanly (kind of) pass errors from the object back to the caller.
-    ///
-    /// # Example
+    /// The largest request envelope, in bytes, the object accepts. Larger
+    /// envelopes, WebSocket frames and plain JSON bodies are rejected with
+    /// [`crate::Error::PayloadTooLarge`] before they're decoded. By default,
+    /// there is no limit beyond the runtime's own.
+    const MAX_ENVELOPE_BYTES: Option<usize> = None;
+
+    /// Validates a request before the object is loaded, after
+    /// [`DoProxy::authorize`]. Invalid requests are rejected with
+    /// [`crate::Error::Validation`] and never reach [`DoProxy::handle`]. By
+    /// default, the request's [`Validate`] impl is used. Override it to add
+    /// rules that only apply to this object.
     ///
     /// ```ignore
-    /// enum PersonError {
-    ///     NotYetBorn,
+    /// fn validate_request(request: &Self::Request) -> Result<(), ValidationErrors> {
+    ///     request.validate()?;
+    ///
+    ///     let mut errors = ValidationErrors::new();
+    ///     if let PersonRequest::Rename { name } = request {
+    ///         if name.len() > 64 {
+    ///             errors.add("name", "must be at most 64 characters");
+    ///         }
+    ///     }
+    ///     errors.into_result()
     /// }
     /// ```
-    type Error: Serialize + DeserializeOwned + Error;
+    fn validate_request(request: &Self::Request) -> Result<(), ValidationErrors> {
+        request.validate()
+    }
+
+    /// Validates init data before it's passed to [`DoProxy::init`], see
+    /// [`DoProxy::validate_request`].
+    fn validate_init(init: &Self::Init) -> Result<(), ValidationErrors> {
+        init.validate()
+    }
 
     /// Called if the object is sent an `init` request. This function may be
     /// called multiple times and implemeting it is _optional_.
@@ -100,6 +177,14 @@ where
     /// generally called only once when the Durable Object first receives a
     /// request. If the object is evicted from memory and then later receives a
     /// request, this function will be called again.
+    ///
+    /// Errors are returned to the caller as they are, like errors returned by
+    /// [`DoProxy::handle`].
+    ///
+    /// When the object is initialized, this is called right after
+    /// [`DoProxy::init`], before the writes `init` buffered with [`Ctx::put`]
+    /// are flushed. Read them with [`Ctx::get`], which sees buffered writes;
+    /// reading through [`worker::State::storage`] doesn't.
     async fn load_from_storage(ctx: &mut Ctx) -> Result<Self, Self::Error>;
 
     /// Called when the object receives a fetch request or an alarm. This is
@@ -111,6 +196,112 @@ where
         req: ProxiedRequest<Self::Request>,
     ) -> Result<Self::Response, Self::Error>;
 
+    /// Whether the object accepts plain JSON requests, e.g. from curl or hurl
+    /// scripts. If enabled, requests without a do-proxy envelope are decoded
+    /// as a bare [`ObjectApi::Request`] instead of being passed to
+    /// [`DoProxy::handle_raw`].
+    ///
+    /// Init data can be sent as JSON in the [`crate::INIT_HEADER`] header or
+    /// the `init` query parameter. A request with init data and an empty body
+    /// only initializes the object.
+    ///
+    /// The object replies with the bare response, or with the error and the
+    /// status returned by [`DoProxy::plain_error_status`]. Failures are
+    /// returned as [`crate::ProblemDetails`].
+    const PLAIN_JSON: bool = false;
+
+    /// The HTTP status of an error returned to a plain JSON request, see
+    /// [`DoProxy::PLAIN_JSON`]. Defaults to `400 Bad Request`.
+    fn plain_error_status(error: &Self::Error) -> u16 {
+        400
+    }
+
+    /// Called for HTTP requests that reach the object without a do-proxy
+    /// envelope, for example a browser or a webhook calling the object
+    /// directly. Requests sent by a [`crate::Proxy`] carry the
+    /// [`crate::ENVELOPE_HEADER`] and never end up here, and neither do `POST`
+    /// requests whose body is an envelope, see
+    /// [`crate::codec::is_request_envelope`].
+    ///
+    /// The object is loaded from storage first, so it must have been
+    /// initialized. By default, every request is rejected with
+    /// `400 Bad Request`.
+    ///
+    /// ```ignore
+    /// async fn handle_raw(&mut self, ctx: &mut Ctx, req: worker::Request) -> worker::Result<worker::Response> {
+    ///     match req.path().as_str() {
+    ///         "/count" => worker::Response::ok(self.count.to_string()),
+    ///         _ => worker::Response::error("not found", 404),
+    ///     }
+    /// }
+    /// ```
+    async fn handle_raw(
+        &mut self,
+        ctx: &mut Ctx,
+        req: worker::Request,
+    ) -> worker::Result<worker::Response> {
+        worker::Response::error("expected a do-proxy request envelope", 400)
+    }
+
+    /// Called when the object receives a request over a WebSocket session
+    /// opened with [`crate::Proxy::connect`]. The returned response, if any, is
+    /// sent back over the same session. Use [`Ctx::push`] and
+    /// [`Ctx::broadcast`] to send events to other sessions.
+    ///
+    /// By default, requests are passed to [`Self::handle`] and every response
+    /// is sent back.
+    async fn on_socket_message(
+        &mut self,
+        ctx: &mut Ctx,
+        session: SessionId,
+        req: Self::Request,
+    ) -> Result<Option<Self::Response>, Self::Error> {
+        self.handle(ctx, ProxiedRequest::Fetch(req)).await.map(Some)
+    }
+
+    /// Called after a WebSocket session was closed. Implementing it is
+    /// _optional_.
+    async fn on_socket_close(&mut self, ctx: &mut Ctx, session: SessionId) {}
+
+    /// Converts a request sent by a caller on `api_version` to the current
+    /// [`ObjectApi::Request`]. Only versions between
+    /// [`ObjectApi::MIN_API_VERSION`] and [`ObjectApi::API_VERSION`] reach this
+    /// function.
+    ///
+    /// Implement it to keep accepting the previous request shapes during a
+    /// rolling deploy. By default, every request is decoded as the current
+    /// type.
+    ///
+    /// ```ignore
+    /// fn upcast_request(api_version: u32, request: Value) -> Result<Self::Request, do_proxy::Error> {
+    ///     match api_version {
+    ///         1 => codec::upcast::<PersonRequestV1, _>(request),
+    ///         _ => codec::upcast::<PersonRequest, _>(request),
+    ///     }
+    /// }
+    /// ```
+    fn upcast_request(api_version: u32, request: Value) -> Result<Self::Request, crate::Error> {
+        codec::upcast::<Self::Request, _>(request)
+    }
+
+    /// Converts a response to the shape a caller on `api_version` expects.
+    /// The counterpart of [`DoProxy::upcast_request`].
+    ///
+    /// ```ignore
+    /// fn downcast_response(api_version: u32, response: Self::Response) -> Result<Value, do_proxy::Error> {
+    ///     match api_version {
+    ///         1 => codec::downcast::<PersonResponseV1, _>(response),
+    ///         _ => codec::downcast::<PersonResponse, _>(response),
+    ///     }
+    /// }
+    /// ```
+    fn downcast_response(
+        api_version: u32,
+        response: Self::Response,
+    ) -> Result<Value, crate::Error> {
+        codec::downcast::<Self::Response, _>(response)
+    }
+
     /// This function wraps the `handle` function and handles the boilerplate of
     /// caching, converting between the different transport types, and error
     /// handling.
@@ -122,67 +313,786 @@ where
         ctx: &mut Ctx,
         req: Option<worker::Request>,
     ) -> worker::Result<worker::Response> {
-        enum TransportOrAlarm<Init, Request> {
-            Transport(RequestTransport<Init, Request>),
-            Alarm,
+        let (api_version, envelope) = match req {
+            Some(req) if Self::ENCODING == Encoding::JsonRpc => {
+                return run_json_rpc(cached_proxy, ctx, req).await
+            }
+            Some(mut req) => {
+                let decoded = read_body::<Self>(&mut req)
+                    .await
+                    .and_then(|body| {
+                        codec::decode_request_with::<Self>(&body, Self::upcast_request)
+                    })
+                    .and_then(|decoded| {
+                        let signed = decoded.principal.as_ref().zip(decoded.digest.as_deref());
+                        ctx.set_principal(authenticate::<Self>(ctx, signed)?);
+                        Ok(decoded)
+                    });
+
+                match decoded {
+                    Ok(decoded) => (decoded.version.api_version, Some(decoded.envelope)),
+                    Err(error) => {
+                        return worker::Response::from_json(&codec::Response::<Self>::Failure {
+                            error,
+                        })
+                    }
+                }
+            }
+            None => (Self::API_VERSION, None),
+        };
+
+        let response = handle_envelope(cached_proxy, ctx, envelope).await;
+        worker::Response::from_json(&downcast::<Self>(api_version, response))
+    }
+}
+
+/// Verifies the principal a request was sent on behalf of, along with the
+/// digest of the request, see [`DoProxy::keyring`]. Returns `None` if the
+/// object has no keyring.
+pub(crate) fn authenticate<O: DoProxy>(
+    ctx: &Ctx,
+    signed: Option<(&SignedPrincipal, &str)>,
+) -> Result<Option<Principal>, crate::Error> {
+    let Some(keyring) = O::keyring(ctx.env)? else {
+        return Ok(None);
+    };
+
+    let Some((signed, digest)) = signed else {
+        return Err(crate::Error::Unauthenticated {
+            reason: "missing principal".to_string(),
+        });
+    };
+    let principal = keyring.verify(O::BINDING, digest, signed)?;
+
+    let id = match &signed.object {
+        ObjectRef::Id(id) => id.clone(),
+        ObjectRef::Name(name) => {
+            let namespace = ctx.env.durable_object(&O::resolve_binding(ctx.env))?;
+            named_id(&namespace, name, O::OBJ_OPTIONS)?
+        }
+    };
+    if id != ctx.state.id().to_string() {
+        return Err(crate::Error::Unauthenticated {
+            reason: "the principal was signed for another object".to_string(),
+        });
+    }
+
+    Ok(Some(principal))
+}
+
+/// Reads the body of a request to `O`, see [`DoProxy::MAX_ENVELOPE_BYTES`].
+pub(crate) async fn read_body<O: DoProxy>(
+    req: &mut worker::Request,
+) -> Result<String, crate::Error> {
+    if let Some(size) = req.headers().get("content-length")? {
+        if let Ok(size) = size.parse() {
+            check_size::<O>(size)?;
+        }
+    }
+
+    let body = req.text().await?;
+    check_size::<O>(body.len())?;
+
+    Ok(body)
+}
+
+/// Checks that a payload of `size` bytes doesn't exceed
+/// [`DoProxy::MAX_ENVELOPE_BYTES`].
+pub(crate) fn check_size<O: DoProxy>(size: usize) -> Result<(), crate::Error> {
+    match O::MAX_ENVELOPE_BYTES {
+        Some(limit) if size > limit => Err(crate::Error::PayloadTooLarge { size, limit }),
+        _ => Ok(()),
+    }
+}
+
+/// Validates the payloads in `envelope`, see [`DoProxy::validate_request`].
+fn validate<O: DoProxy>(envelope: &codec::Request<O>) -> Result<(), crate::Error> {
+    match envelope {
+        RequestTransport::InitWithRequest { init, request } => {
+            O::validate_init(init)?;
+            O::validate_request(request)?;
+        }
+        RequestTransport::Init { init } => O::validate_init(init)?,
+        RequestTransport::Request { request } => O::validate_request(request)?,
+        RequestTransport::Empty => {}
+    }
+
+    Ok(())
+}
+
+/// Checks that the caller may send `envelope`, see [`DoProxy::authorize`].
+async fn authorize<O: DoProxy>(
+    ctx: &Ctx<'_>,
+    envelope: &codec::Request<O>,
+) -> Result<(), crate::Error> {
+    match envelope {
+        RequestTransport::InitWithRequest { init, request } => {
+            authorize_init::<O>(ctx, init).await?;
+            authorize_request::<O>(ctx, request).await
+        }
+        RequestTransport::Init { init } => authorize_init::<O>(ctx, init).await,
+        RequestTransport::Request { request } => authorize_request::<O>(ctx, request).await,
+        RequestTransport::Empty => Ok(()),
+    }
+}
+
+async fn authorize_init<O: DoProxy>(ctx: &Ctx<'_>, init: &O::Init) -> Result<(), crate::Error> {
+    let outcome = O::authorize_init(ctx, ctx.principal(), init).await;
+    O::audit(
+        ctx,
+        AuditRecord::new(ctx.principal(), Some(INIT_COMMAND), &outcome),
+    )
+    .await;
+    outcome
+}
+
+/// Checks that the caller may send `request`, see [`DoProxy::authorize`].
+pub(crate) async fn authorize_request<O: DoProxy>(
+    ctx: &Ctx<'_>,
+    request: &O::Request,
+) -> Result<(), crate::Error> {
+    let outcome = O::authorize(ctx, ctx.principal(), request).await;
+    let command = codec::command_name(request);
+    O::audit(ctx, AuditRecord::new(ctx.principal(), command, &outcome)).await;
+    outcome
+}
+
+/// Handles a decoded request envelope, or an alarm if `envelope` is `None`.
+///
+/// The object is initialized if the envelope carries init data and the object
+/// isn't cached yet, and loaded from storage if it isn't cached.
+///
+/// Writes buffered on the context are flushed if the envelope was handled
+/// successfully and discarded otherwise, see [`Ctx::put`].
+pub(crate) async fn handle_envelope<O: DoProxy>(
+    cached_proxy: &mut Option<O>,
+    ctx: &mut Ctx<'_>,
+    envelope: Option<codec::Request<O>>,
+) -> codec::Response<O> {
+    let response = dispatch_envelope(cached_proxy, ctx, envelope).await;
+
+    let flushed = match response {
+        ResponseTransport::Response { .. } | ResponseTransport::Initialized => {
+            ctx.flush_writes().await
+        }
+        ResponseTransport::Error { .. } | ResponseTransport::Failure { .. } => {
+            ctx.discard_writes();
+            Ok(())
+        }
+    };
+
+    settle_writes(cached_proxy, response, flushed)
+}
+
+/// Returns `response`, or a failure if its writes couldn't be flushed.
+fn settle_writes<O: DoProxy>(
+    cached_proxy: &mut Option<O>,
+    response: codec::Response<O>,
+    flushed: Result<(), crate::Error>,
+) -> codec::Response<O> {
+    match flushed {
+        Ok(()) => response,
+        Err(error) => {
+            // The cached object may not match storage anymore.
+            *cached_proxy = None;
+            ResponseTransport::Failure { error }
         }
+    }
+}
 
-        let mut transport_or_alarm: TransportOrAlarm<Self::Init, Self::Request> = match req {
-            Some(mut req) => TransportOrAlarm::Transport(req.json().await?),
-            None => TransportOrAlarm::Alarm,
+async fn dispatch_envelope<O: DoProxy>(
+    cached_proxy: &mut Option<O>,
+    ctx: &mut Ctx<'_>,
+    mut envelope: Option<codec::Request<O>>,
+) -> codec::Response<O> {
+    if let Some(envelope) = &envelope {
+        let checked = match authorize::<O>(ctx, envelope).await {
+            Ok(()) => validate::<O>(envelope),
+            Err(error) => Err(error),
         };
+        if let Err(error) = checked {
+            return ResponseTransport::Failure { error };
+        }
+    }
 
-        let mut proxy = match cached_proxy.take() {
-            Some(proxy) => proxy,
-            None => {
-                if let Some(init) = match transport_or_alarm {
-                    TransportOrAlarm::Transport(ref mut transport) => transport.take_init(),
-                    TransportOrAlarm::Alarm => None,
-                } {
-                    Self::init(ctx, init).await.map_err(|e| e.to_string())?;
-                    Self::load_from_storage(ctx)
-                        .await
-                        .map_err(|e| e.to_string())?
-                } else {
-                    Self::load_from_storage(ctx)
-                        .await
-                        .map_err(|e| e.to_string())?
+    let init = envelope.as_mut().and_then(RequestTransport::take_init);
+
+    let mut proxy = match cached_proxy.take() {
+        Some(proxy) => proxy,
+        None => {
+            if let Some(init) = init {
+                if let Err(error) = O::init(ctx, init).await {
+                    return ResponseTransport::Error { error };
                 }
             }
-        };
 
-        let response = match transport_or_alarm {
-            TransportOrAlarm::Transport(RequestTransport::Request { request }) => {
-                match proxy.handle(ctx, ProxiedRequest::Fetch(request)).await {
-                    Ok(response) => ResponseTransport::Response { response },
-                    Err(error) => ResponseTransport::Error { error },
-                }
+            match O::load_from_storage(ctx).await {
+                Ok(proxy) => proxy,
+                Err(error) => return ResponseTransport::Error { error },
             }
-            TransportOrAlarm::Alarm => match proxy.handle(ctx, ProxiedRequest::Alarm).await {
-                Ok(response) => ResponseTransport::Response { response },
-                Err(error) => ResponseTransport::Error { error },
-            },
-            TransportOrAlarm::Transport(RequestTransport::Empty) => ResponseTransport::Initialized,
-            _ => {
-                unreachable!("RequestTransport::Init and RequestTransport::InitWithRequest should have been handled by the match arm above");
+        }
+    };
+
+    let request = match envelope {
+        Some(RequestTransport::Request { request }) => ProxiedRequest::Fetch(request),
+        None => ProxiedRequest::Alarm,
+        Some(_) => {
+            *cached_proxy = Some(proxy);
+            return ResponseTransport::Initialized;
+        }
+    };
+
+    let response = match proxy.handle(ctx, request).await {
+        Ok(response) => ResponseTransport::Response { response },
+        Err(error) => ResponseTransport::Error { error },
+    };
+
+    *cached_proxy = Some(proxy);
+    response
+}
+
+/// Handles a JSON-RPC request, see [`Encoding::JsonRpc`]. The calls of a
+/// batch are handled one after the other, in order.
+async fn run_json_rpc<O: DoProxy>(
+    cached_proxy: &mut Option<O>,
+    ctx: &mut Ctx<'_>,
+    mut req: worker::Request,
+) -> worker::Result<worker::Response> {
+    let mut headers = worker::Headers::new();
+    headers.set("content-type", "application/json")?;
+
+    let calls = match authenticate::<O>(ctx, None) {
+        Ok(_) => read_body::<O>(&mut req)
+            .await
+            .and_then(|body| jsonrpc::decode_requests::<O>(&body, O::upcast_request)),
+        Err(error) => Err(error),
+    };
+    let calls = match calls {
+        Ok(calls) => calls,
+        Err(error) => {
+            let body = jsonrpc::encode_failure(&error)?;
+            return Ok(worker::Response::ok(body)?.with_headers(headers));
+        }
+    };
+
+    let mut responses = Vec::with_capacity(calls.calls.len());
+    for call in calls.calls {
+        let response = match call.request {
+            Ok(envelope) => {
+                let response = handle_envelope(cached_proxy, ctx, Some(envelope)).await;
+                downcast::<O>(O::API_VERSION, response)
             }
+            Err(error) => ResponseTransport::Failure { error },
         };
+        responses.push((call.id, response));
+    }
 
-        *cached_proxy = Some(proxy);
-        worker::Response::from_json(&response)
+    match jsonrpc::encode_responses(calls.batch, responses)? {
+        Some(body) => Ok(worker::Response::ok(body)?.with_headers(headers)),
+        None => worker::Response::empty(),
     }
 }
 
+/// Converts the response in `response` for a caller on `api_version`, see
+/// [`DoProxy::downcast_response`].
+pub(crate) fn downcast<O: DoProxy>(
+    api_version: u32,
+    response: codec::Response<O>,
+) -> ResponseTransport<Value, O::Error> {
+    match response {
+        ResponseTransport::Response { response } => {
+            match O::downcast_response(api_version, response) {
+                Ok(response) => ResponseTransport::Response { response },
+                Err(error) => ResponseTransport::Failure { error },
+            }
+        }
+        ResponseTransport::Error { error } => ResponseTransport::Error { error },
+        ResponseTransport::Initialized => ResponseTransport::Initialized,
+        ResponseTransport::Failure { error } => ResponseTransport::Failure { error },
+    }
+}
+
+fn decode_value<T: DeserializeOwned>(value: &Value) -> Result<T, crate::Error> {
+    T::deserialize(value).map_err(|err| crate::Error::decode(&err, &value.to_string()))
+}
+
+/// The field of the value a buffered delete is flushed as, see
+/// [`Ctx::flush_writes`].
+const TOMBSTONE_FIELD: &str = "__do_proxy_deleted";
+
+fn tombstone() -> Value {
+    serde_json::json!({ TOMBSTONE_FIELD: true })
+}
+
+fn is_tombstone(value: &Value) -> bool {
+    *value == tombstone()
+}
+
+/// Splits buffered writes into the values to put, with deletes written as
+/// tombstones, and the keys to remove afterwards.
+fn write_batch(
+    writes: BTreeMap<String, Option<Value>>,
+) -> (serde_json::Map<String, Value>, Vec<String>) {
+    let mut values = serde_json::Map::new();
+    let mut deleted = Vec::new();
+    for (key, value) in writes {
+        match value {
+            Some(value) => {
+                values.insert(key, value);
+            }
+            None => {
+                values.insert(key.clone(), tombstone());
+                deleted.push(key);
+            }
+        }
+    }
+
+    (values, deleted)
+}
+
 /// The context that is passed to the object's `init`, `load_from_storage`, and `handle` functions.
 ///
-/// Wraps [`worker::State`] and [`worker::Env`].
+/// Wraps [`worker::State`] and [`worker::Env`], and gives access to the
+/// object's WebSocket sessions.
 pub struct Ctx<'s> {
     pub state: &'s State,
     pub env: &'s Env,
+    sessions: Option<&'s Sessions>,
+    session: Option<SessionId>,
+    principal: Option<Principal>,
+    writes: BTreeMap<String, Option<Value>>,
 }
 
 impl<'s> Ctx<'s> {
     pub fn new(state: &'s State, env: &'s Env) -> Self {
-        Self { state, env }
+        Self {
+            state,
+            env,
+            sessions: None,
+            session: None,
+            principal: None,
+            writes: BTreeMap::new(),
+        }
+    }
+
+    /// Gives the context access to the object's WebSocket sessions.
+    pub fn with_sessions(mut self, sessions: &'s Sessions) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    pub(crate) fn with_session(mut self, session: SessionId) -> Self {
+        self.session = Some(session);
+        self
+    }
+
+    /// The session the current request was received on, if it was received
+    /// over a WebSocket.
+    pub fn session(&self) -> Option<SessionId> {
+        self.session
+    }
+
+    /// The verified principal the current request was sent on behalf of.
+    /// Always `None` if the object has no keyring, see [`DoProxy::keyring`].
+    pub fn principal(&self) -> Option<&Principal> {
+        self.principal.as_ref()
+    }
+
+    pub(crate) fn set_principal(&mut self, principal: Option<Principal>) {
+        self.principal = principal;
+    }
+
+    /// Buffers a write of `value` under `key`. Buffered writes are flushed
+    /// together when the request has been handled successfully, and discarded
+    /// if it fails, so a failed command leaves no partial writes behind.
+    /// Writes made directly through [`worker::State::storage`] aren't
+    /// buffered, and reads made through it don't see buffered writes, so read
+    /// values written with `put` using [`Ctx::get`]. This includes values
+    /// written by [`DoProxy::init`] and read by [`DoProxy::load_from_storage`].
+    ///
+    /// ```ignore
+    /// ctx.put("name", &name)?;
+    /// ctx.delete("nickname");
+    /// ```
+    pub fn put<T: Serialize>(
+        &mut self,
+        key: impl Into<String>,
+        value: &T,
+    ) -> Result<(), crate::Error> {
+        let value = serde_json::to_value(value).map_err(|err| crate::Error::encode(&err))?;
+        self.writes.insert(key.into(), Some(value));
+        Ok(())
+    }
+
+    /// Buffers a delete of `key`, see [`Ctx::put`]. Until it's removed, a
+    /// deleted key holds a tombstone in storage, which [`Ctx::get`] reads as
+    /// a missing value.
+    pub fn delete(&mut self, key: impl Into<String>) {
+        self.writes.insert(key.into(), None);
+    }
+
+    /// Reads the value under `key`, including writes buffered by the current
+    /// request. Returns `None` if there is no value, and fails if storage
+    /// can't be read or the value isn't a `T`.
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, crate::Error> {
+        match self.writes.get(key) {
+            Some(Some(value)) => decode_value(value).map(Some),
+            Some(None) => Ok(None),
+            None => match self.stored(key).await? {
+                Some(value) => decode_value(&value).map(Some),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// Reads the value under `key` from storage. `Storage::get` fails the same
+    /// way for missing keys as for any other error, so the value is read with
+    /// `get_multiple`, which leaves missing keys out.
+    async fn stored(&self, key: &str) -> Result<Option<Value>, crate::Error> {
+        let values = self.state.storage().get_multiple(vec![key]).await?;
+        let value = values.get(&JsValue::from(key));
+        if value.is_undefined() {
+            return Ok(None);
+        }
+
+        let json = String::from(JSON::stringify(&value).map_err(worker::Error::from)?);
+        let value: Value =
+            serde_json::from_str(&json).map_err(|err| crate::Error::decode(&err, &json))?;
+
+        Ok((!is_tombstone(&value)).then_some(value))
+    }
+
+    /// Flushes the buffered writes with a single `put_multiple`, which the
+    /// runtime commits atomically. Deletes are written as tombstones, so they
+    /// are committed together with the puts, and the tombstones are removed
+    /// afterwards. If removing them fails, they stay behind as missing values.
+    pub(crate) async fn flush_writes(&mut self) -> Result<(), crate::Error> {
+        let (values, deleted) = write_batch(std::mem::take(&mut self.writes));
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let mut storage = self.state.storage();
+        storage.put_multiple(Value::Object(values)).await?;
+        if !deleted.is_empty() {
+            // The deletes are already committed, this only frees the space.
+            let _ = storage.delete_multiple(deleted).await;
+        }
+
+        Ok(())
+    }
+
+    /// Drops the buffered writes, see [`Ctx::put`].
+    pub(crate) fn discard_writes(&mut self) {
+        self.writes.clear();
+    }
+
+    /// The IDs of all WebSocket sessions connected to the object.
+    pub fn sessions(&self) -> Vec<SessionId> {
+        self.sessions.map(Sessions::ids).unwrap_or_default()
+    }
+
+    /// Pushes an event to one session. Events should be the object's
+    /// [`ObjectApi::Response`] type, which is how callers decode them.
+    ///
+    /// ```ignore
+    /// ctx.push(session, &ChatResponse::Joined { user })?;
+    /// ```
+    pub fn push<E: Serialize>(&self, session: SessionId, event: &E) -> Result<(), crate::Error> {
+        match self.sessions {
+            Some(sessions) => sessions.send(session, &event_frame(event)?),
+            None => Err(crate::Error::worker(format!(
+                "no session with id {session}"
+            ))),
+        }
+    }
+
+    /// Pushes an event to every session, see [`Ctx::push`].
+    pub fn broadcast<E: Serialize>(&self, event: &E) -> Result<(), crate::Error> {
+        match self.sessions {
+            Some(sessions) => sessions.broadcast(&event_frame(event)?),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+    use serde::Deserialize;
+    use worker::{wasm_bindgen::JsCast, worker_sys::ObjectState};
+
+    use super::*;
+
+    /// Calls `f` with a context whose state and env aren't backed by the
+    /// runtime, so only code that never touches them can run.
+    fn with_ctx<T>(f: impl FnOnce(&mut Ctx) -> T) -> T {
+        let state = State::from(JsValue::NULL.unchecked_into::<ObjectState>());
+        let env: Env = JsValue::NULL.unchecked_into();
+        f(&mut Ctx::new(&state, &env))
+    }
+
+    struct Limited;
+
+    impl ObjectApi for Limited {
+        const BINDING: &'static str = "LIMITED";
+
+        type Init = ();
+        type Request = ();
+        type Response = ();
+        type Error = crate::Error;
+    }
+
+    #[async_trait(?Send)]
+    impl DoProxy for Limited {
+        const MAX_ENVELOPE_BYTES: Option<usize> = Some(1024);
+
+        async fn load_from_storage(_ctx: &mut Ctx) -> Result<Self, Self::Error> {
+            Ok(Self)
+        }
+
+        async fn handle(
+            &mut self,
+            _ctx: &mut Ctx,
+            _req: ProxiedRequest<Self::Request>,
+        ) -> Result<Self::Response, Self::Error> {
+            Ok(())
+        }
+    }
+
+    struct Unlimited;
+
+    impl ObjectApi for Unlimited {
+        const BINDING: &'static str = "UNLIMITED";
+
+        type Init = ();
+        type Request = ();
+        type Response = ();
+        type Error = crate::Error;
+    }
+
+    #[async_trait(?Send)]
+    impl DoProxy for Unlimited {
+        async fn load_from_storage(_ctx: &mut Ctx) -> Result<Self, Self::Error> {
+            Ok(Self)
+        }
+
+        async fn handle(
+            &mut self,
+            _ctx: &mut Ctx,
+            _req: ProxiedRequest<Self::Request>,
+        ) -> Result<Self::Response, Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn accepts_envelopes_up_to_the_limit() {
+        assert!(check_size::<Limited>(0).is_ok());
+        assert!(check_size::<Limited>(1023).is_ok());
+        assert!(check_size::<Limited>(1024).is_ok());
+    }
+
+    #[test]
+    fn rejects_envelopes_over_the_limit() {
+        assert_eq!(
+            check_size::<Limited>(1025),
+            Err(crate::Error::PayloadTooLarge {
+                size: 1025,
+                limit: 1024,
+            })
+        );
+    }
+
+    #[test]
+    fn accepts_any_size_without_a_limit() {
+        assert!(check_size::<Unlimited>(usize::MAX).is_ok());
+    }
+
+    /// Panics when loaded, requests must be rejected before that.
+    struct Strict;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    enum StrictRequest {
+        Rename { name: String },
+    }
+
+    impl Validate for StrictRequest {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            let StrictRequest::Rename { name } = self;
+            if name.is_empty() {
+                errors.add("name", "must not be empty");
+            }
+            errors.into_result()
+        }
+    }
+
+    impl ObjectApi for Strict {
+        const BINDING: &'static str = "STRICT";
+
+        type Init = ();
+        type Request = StrictRequest;
+        type Response = ();
+        type Error = crate::Error;
+    }
+
+    #[async_trait(?Send)]
+    impl DoProxy for Strict {
+        async fn load_from_storage(_ctx: &mut Ctx) -> Result<Self, Self::Error> {
+            panic!("the object was loaded");
+        }
+
+        async fn handle(
+            &mut self,
+            _ctx: &mut Ctx,
+            _req: ProxiedRequest<Self::Request>,
+        ) -> Result<Self::Response, Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn invalid_requests_are_rejected_before_the_object_is_loaded() {
+        let envelope = RequestTransport::Request {
+            request: StrictRequest::Rename {
+                name: String::new(),
+            },
+        };
+
+        let mut cached = None;
+        let response =
+            with_ctx(|ctx| block_on(handle_envelope::<Strict>(&mut cached, ctx, Some(envelope))));
+
+        let ResponseTransport::Failure { error } = response else {
+            panic!("expected a failure");
+        };
+        assert_eq!(
+            error,
+            crate::Error::Validation {
+                fields: vec![crate::FieldError {
+                    field: "name".to_string(),
+                    message: "must not be empty".to_string(),
+                }],
+            }
+        );
+        assert!(cached.is_none());
+    }
+
+    /// Buffers a write, then fails.
+    struct Failing;
+
+    impl ObjectApi for Failing {
+        const BINDING: &'static str = "FAILING";
+
+        type Init = ();
+        type Request = ();
+        type Response = ();
+        type Error = crate::Error;
+    }
+
+    #[async_trait(?Send)]
+    impl DoProxy for Failing {
+        async fn load_from_storage(_ctx: &mut Ctx) -> Result<Self, Self::Error> {
+            Ok(Self)
+        }
+
+        async fn handle(
+            &mut self,
+            ctx: &mut Ctx,
+            _req: ProxiedRequest<Self::Request>,
+        ) -> Result<Self::Response, Self::Error> {
+            ctx.put("count", &1)?;
+            Err(crate::Error::worker("the command failed"))
+        }
+    }
+
+    #[test]
+    fn get_sees_buffered_writes() {
+        with_ctx(|ctx| {
+            ctx.put("name", &"Ada").unwrap();
+            ctx.put("nickname", &"A").unwrap();
+            ctx.delete("nickname");
+            ctx.delete("age");
+
+            assert_eq!(
+                block_on(ctx.get::<String>("name")).unwrap(),
+                Some("Ada".to_string())
+            );
+            assert_eq!(block_on(ctx.get::<String>("nickname")).unwrap(), None);
+            assert_eq!(block_on(ctx.get::<u32>("age")).unwrap(), None);
+            assert!(block_on(ctx.get::<u32>("name")).is_err());
+        });
+    }
+
+    #[test]
+    fn errors_discard_buffered_writes() {
+        with_ctx(|ctx| {
+            let mut cached = None;
+            let envelope = RequestTransport::Request { request: () };
+            let response = block_on(handle_envelope::<Failing>(&mut cached, ctx, Some(envelope)));
+
+            assert!(matches!(response, ResponseTransport::Error { .. }));
+            assert!(ctx.writes.is_empty());
+        });
+    }
+
+    #[test]
+    fn failures_discard_buffered_writes() {
+        with_ctx(|ctx| {
+            ctx.put("name", &"Ada").unwrap();
+
+            let mut cached = None;
+            let envelope = RequestTransport::Request {
+                request: StrictRequest::Rename {
+                    name: String::new(),
+                },
+            };
+            let response = block_on(handle_envelope::<Strict>(&mut cached, ctx, Some(envelope)));
+
+            assert!(matches!(response, ResponseTransport::Failure { .. }));
+            assert!(ctx.writes.is_empty());
+        });
+    }
+
+    #[test]
+    fn failed_flushes_evict_the_cached_object() {
+        let mut cached = Some(Unlimited);
+        let response = settle_writes(
+            &mut cached,
+            ResponseTransport::Response { response: () },
+            Err(crate::Error::worker("storage is gone")),
+        );
+
+        assert!(matches!(
+            response,
+            ResponseTransport::Failure {
+                error: crate::Error::Worker { .. }
+            }
+        ));
+        assert!(cached.is_none());
+
+        let mut cached = Some(Unlimited);
+        let response = settle_writes(&mut cached, ResponseTransport::Initialized, Ok(()));
+
+        assert!(matches!(response, ResponseTransport::Initialized));
+        assert!(cached.is_some());
+    }
+
+    #[test]
+    fn deletes_are_flushed_as_tombstones() {
+        let mut writes = BTreeMap::new();
+        writes.insert("name".to_string(), Some(Value::from("Ada")));
+        writes.insert("nickname".to_string(), None);
+
+        let (values, deleted) = write_batch(writes);
+
+        assert_eq!(values["name"], "Ada");
+        assert!(is_tombstone(&values["nickname"]));
+        assert_eq!(deleted, ["nickname"]);
+        assert!(!is_tombstone(&Value::Null));
+        assert!(!is_tombstone(
+            &serde_json::json!({ TOMBSTONE_FIELD: false })
+        ));
     }
 }
@@ -0,0 +1,219 @@
+use std::{future::Future, pin::Pin};
+
+use async_trait::async_trait;
+use worker::{Fetcher, Headers, Method, Request, RequestInit, Response, Stub};
+
+use crate::{
+    auth::ObjectRef,
+    transport::{BINDING_HEADER, ENVELOPE_HEADER, NAME_HEADER},
+};
+
+/// Delivers serialized request envelopes to an object and returns the
+/// serialized response envelopes.
+///
+/// [`crate::Proxy`] is generic over its transport, so the typed builder works
+/// the same no matter how the envelope reaches the object. The crate comes
+/// with transports for stubs ([`StubTransport`]), service bindings
+/// ([`ServiceTransport`]), gateways reachable over HTTP ([`HttpTransport`])
+/// and in-process handlers ([`Loopback`]). Use
+/// [`crate::Proxy::with_transport`] to create a proxy with any of them.
+#[async_trait(?Send)]
+pub trait Transport {
+    /// Sends `body`, a request envelope for an object bound under `binding`,
+    /// and returns the object's response envelope.
+    async fn deliver(&self, binding: &str, body: String) -> Result<String, crate::Error>;
+
+    /// The object requests are delivered to, which principals sent through
+    /// the transport are signed for, see [`crate::Proxy::with_principal`].
+    /// Transports that don't know their object can't send principals.
+    fn object(&self) -> Option<ObjectRef> {
+        None
+    }
+}
+
+/// Delivers requests through a [`worker::Stub`]. This is the transport of
+/// proxies created with [`crate::EnvExt`].
+pub struct StubTransport {
+    stub: Stub,
+    object: Option<ObjectRef>,
+}
+
+impl StubTransport {
+    pub fn new(stub: Stub) -> Self {
+        Self { stub, object: None }
+    }
+
+    /// Signs principals for `object`, which must be the object behind the
+    /// stub. Stubs created with [`crate::EnvExt`] know their object's ID.
+    pub fn with_object(mut self, object: ObjectRef) -> Self {
+        self.object = Some(object);
+        self
+    }
+
+    pub(crate) fn stub(&self) -> &Stub {
+        &self.stub
+    }
+}
+
+#[async_trait(?Send)]
+impl Transport for StubTransport {
+    async fn deliver(&self, binding: &str, body: String) -> Result<String, crate::Error> {
+        let request = envelope_request(&format!("http://{binding}/"), body, None)?;
+        let response = self
+            .stub
+            .fetch_with_request(request)
+            .await
+            .map_err(|err| crate::Error::stub_fetch(&err))?;
+        response_body(response).await
+    }
+
+    fn object(&self) -> Option<ObjectRef> {
+        self.object.clone()
+    }
+}
+
+/// Delivers requests through a service binding to the worker that owns the
+/// object. The worker is expected to route them with a
+/// [`crate::ObjectRouter`].
+pub struct ServiceTransport {
+    fetcher: Fetcher,
+    name: String,
+}
+
+impl ServiceTransport {
+    /// Creates a transport to the object with the given name behind `fetcher`.
+    pub fn new(fetcher: Fetcher, name: impl Into<String>) -> Self {
+        Self {
+            fetcher,
+            name: name.into(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Transport for ServiceTransport {
+    async fn deliver(&self, binding: &str, body: String) -> Result<String, crate::Error> {
+        let request = envelope_request(
+            &format!("http://{binding}/"),
+            body,
+            Some(routing_headers(binding, &self.name)?),
+        )?;
+        let response = self
+            .fetcher
+            .fetch_request(request)
+            .await
+            .map_err(|err| crate::Error::stub_fetch(&err))?;
+        response_body(response).await
+    }
+
+    fn object(&self) -> Option<ObjectRef> {
+        Some(ObjectRef::Name(self.name.clone()))
+    }
+}
+
+/// Delivers requests over HTTP to a gateway worker that routes them with a
+/// [`crate::ObjectRouter`], for example a gateway in another account.
+pub struct HttpTransport {
+    url: String,
+    name: String,
+}
+
+impl HttpTransport {
+    /// Creates a transport to the object with the given name behind the
+    /// gateway at `gateway_url`.
+    pub fn new(gateway_url: impl Into<String>, name: impl Into<String>) -> Self {
+        Self {
+            url: gateway_url.into(),
+            name: name.into(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Transport for HttpTransport {
+    async fn deliver(&self, binding: &str, body: String) -> Result<String, crate::Error> {
+        let request =
+            envelope_request(&self.url, body, Some(routing_headers(binding, &self.name)?))?;
+        let response = worker::Fetch::Request(request)
+            .send()
+            .await
+            .map_err(|err| crate::Error::stub_fetch(&err))?;
+        response_body(response).await
+    }
+
+    fn object(&self) -> Option<ObjectRef> {
+        Some(ObjectRef::Name(self.name.clone()))
+    }
+}
+
+/// The future returned by a [`Loopback`] handler.
+pub type LoopbackFuture = Pin<Box<dyn Future<Output = Result<String, crate::Error>>>>;
+
+/// Delivers requests to a handler in the same process, for example a fake
+/// object in tests.
+///
+/// # Example
+///
+/// ```ignore
+/// let transport = Loopback::new(|_binding, _body| {
+///     Box::pin(async move { Ok(r#"{"type":"initialized"}"#.to_string()) })
+/// });
+/// let proxy = Proxy::<Inserter, _>::with_transport(transport, "INSERTER_OBJECT");
+/// ```
+pub struct Loopback<F> {
+    handler: F,
+}
+
+impl<F> Loopback<F>
+where
+    F: Fn(&str, String) -> LoopbackFuture,
+{
+    pub fn new(handler: F) -> Self {
+        Self { handler }
+    }
+}
+
+#[async_trait(?Send)]
+impl<F> Transport for Loopback<F>
+where
+    F: Fn(&str, String) -> LoopbackFuture,
+{
+    async fn deliver(&self, binding: &str, body: String) -> Result<String, crate::Error> {
+        (self.handler)(binding, body).await
+    }
+}
+
+fn routing_headers(binding: &str, name: &str) -> worker::Result<Headers> {
+    let mut headers = Headers::new();
+    headers.set(BINDING_HEADER, binding)?;
+    headers.set(NAME_HEADER, name)?;
+
+    Ok(headers)
+}
+
+fn envelope_request(url: &str, body: String, headers: Option<Headers>) -> worker::Result<Request> {
+    let mut headers = headers.unwrap_or_default();
+    headers.set(ENVELOPE_HEADER, "1")?;
+
+    let mut request_init = RequestInit::new();
+    request_init
+        .with_method(Method::Post)
+        .with_headers(headers)
+        .with_body(Some(body.into()));
+
+    Request::new_with_init(url, &request_init)
+}
+
+async fn response_body(mut response: Response) -> Result<String, crate::Error> {
+    let status = response.status_code();
+    let body = response
+        .text()
+        .await
+        .map_err(|err| crate::Error::stub_fetch(&err))?;
+
+    if !(200..300).contains(&status) {
+        return Err(crate::Error::unexpected_status(status, &body));
+    }
+
+    Ok(body)
+}
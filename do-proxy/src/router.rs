@@ -0,0 +1,161 @@
+use worker::{Env, Headers, Request, Response};
+
+use crate::{
+    options::named_transport,
+    transport::{BINDING_HEADER, NAME_HEADER},
+    DoProxy, EnvExt, ObjOptions, Transport,
+};
+
+/// Routes requests sent by proxies in other workers or services to the right
+/// durable object.
+///
+/// Proxies created with [`crate::EnvExt::obj_via_service`] and `HttpProxy`s
+/// send their requests to the worker that owns the object. That worker then uses an
+/// [`ObjectRouter`] to forward each request to the object named in its
+/// headers. Only bindings that were registered with the router can be reached.
+///
+/// # Example
+///
+/// ```ignore
+/// #[worker::event(fetch, respond_with_errors)]
+/// pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Response> {
+///     if ObjectRouter::is_routable(&req) {
+///         return ObjectRouter::new()
+///             .object::<Inserter>()
+///             .route(req, &env)
+///             .await;
+///     }
+///
+///     // ... handle all other requests
+/// }
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ObjectRouter {
+    routes: Vec<Route>,
+}
+
+#[derive(Debug, Clone)]
+struct Route {
+    binding: RouteBinding,
+    options: ObjOptions,
+}
+
+#[derive(Debug, Clone)]
+enum RouteBinding {
+    Fixed(String),
+    /// Resolved from the environment of each routed request, like
+    /// [`DoProxy::resolve_binding`].
+    Resolved(fn(&Env) -> String),
+}
+
+impl Route {
+    fn binding(&self, env: &Env) -> String {
+        match &self.binding {
+            RouteBinding::Fixed(binding) => binding.clone(),
+            RouteBinding::Resolved(resolve) => resolve(env),
+        }
+    }
+}
+
+impl ObjectRouter {
+    /// Creates a router that doesn't route to any binding.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows routing to objects of type `O` under the binding returned by
+    /// [`DoProxy::resolve_binding`], the one proxies to `O` send requests for.
+    /// Objects are created with [`DoProxy::OBJ_OPTIONS`].
+    pub fn object<O: DoProxy>(mut self) -> Self {
+        self.routes.push(Route {
+            binding: RouteBinding::Resolved(O::resolve_binding),
+            options: O::OBJ_OPTIONS,
+        });
+        self
+    }
+
+    /// Allows routing to objects under `binding`. Use this for objects that are
+    /// bound under more than one name, see [`crate::EnvExt::obj_in_binding`].
+    pub fn binding(self, binding: impl Into<String>) -> Self {
+        self.binding_with(binding, ObjOptions::new())
+    }
+
+    /// Allows routing to objects under `binding`, created according to
+    /// `options`.
+    pub fn binding_with(mut self, binding: impl Into<String>, options: ObjOptions) -> Self {
+        self.routes.push(Route {
+            binding: RouteBinding::Fixed(binding.into()),
+            options,
+        });
+        self
+    }
+
+    /// Returns `true` if `req` was sent by a proxy and should be routed.
+    pub fn is_routable(req: &Request) -> bool {
+        matches!(req.headers().get(BINDING_HEADER), Ok(Some(_)))
+    }
+
+    /// Forwards `req` to the object named by its headers and returns the
+    /// object's response.
+    ///
+    /// Requests without the routing headers fail with `400 Bad Request` and
+    /// requests for bindings that weren't registered fail with `404 Not Found`.
+    pub async fn route(&self, mut req: Request, env: &Env) -> worker::Result<Response> {
+        let (binding, name) = match (
+            req.headers().get(BINDING_HEADER)?,
+            req.headers().get(NAME_HEADER)?,
+        ) {
+            (Some(binding), Some(name)) => (binding, name),
+            _ => return Response::error("missing do-proxy routing headers", 400),
+        };
+
+        let Some(route) = self
+            .routes
+            .iter()
+            .find(|route| route.binding(env) == binding)
+        else {
+            return Response::error(format!("unknown binding `{binding}`"), 404);
+        };
+
+        let namespace = env.durable_object(&binding)?;
+        let transport = named_transport(&namespace, &name, route.options)?;
+
+        envelope_response(transport.deliver(&binding, req.text().await?).await)
+    }
+}
+
+/// Forwards a request envelope sent by a proxy, for example an
+/// `HttpProxy`, to the object of type `O` with the given name and returns the
+/// object's response.
+///
+/// Use this in gateways that choose the object themselves instead of trusting
+/// the routing headers, see [`ObjectRouter`] for the latter.
+///
+/// # Example
+///
+/// ```ignore
+/// #[worker::event(fetch, respond_with_errors)]
+/// pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Response> {
+///     let name = req.path();
+///     forward_envelope::<Inserter>(req, &env, &name).await
+/// }
+/// ```
+pub async fn forward_envelope<O: DoProxy>(
+    mut req: Request,
+    env: &Env,
+    name: &str,
+) -> worker::Result<Response> {
+    let proxy = env.obj::<O>(name)?;
+    envelope_response(proxy.forward(req.text().await?).await)
+}
+
+fn envelope_response(envelope: Result<String, crate::Error>) -> worker::Result<Response> {
+    match envelope {
+        Ok(body) => {
+            let mut headers = Headers::new();
+            headers.set("content-type", "application/json")?;
+            Ok(Response::ok(body)?.with_headers(headers))
+        }
+        Err(error) => Response::error(error.to_string(), 502),
+    }
+}
@@ -0,0 +1,246 @@
+use std::{future::Future, marker::PhantomData};
+
+use worker::ObjectNamespace;
+
+use crate::{options::named_transport, DoProxy, Proxy};
+
+/// A group of `shard_count` objects of the same type that share a name
+/// `prefix`. Keys are mapped onto a shard with a consistent hash, so every
+/// caller that uses the same prefix and shard count will always reach the same
+/// object for a given key.
+///
+/// Create sharded proxies using [`crate::EnvExt::sharded`].
+///
+/// Shards are named `{prefix}-{index}`, where `index` is in
+/// `0..shard_count`.
+///
+/// # Example
+///
+/// ```ignore
+/// let counters = env.sharded::<Counter>("page_views", 16)?;
+///
+/// // All increments for `/index.html` go to the same shard.
+/// counters.get("/index.html")?.send(Command::Increment).await?;
+/// ```
+pub struct ShardedProxy<O> {
+    namespace: ObjectNamespace,
+    binding: String,
+    prefix: String,
+    shard_count: u32,
+    _phantom: PhantomData<O>,
+}
+
+impl<O: DoProxy> ShardedProxy<O> {
+    pub(crate) fn new(
+        namespace: ObjectNamespace,
+        binding: String,
+        prefix: &str,
+        shard_count: u32,
+    ) -> Result<Self, worker::Error> {
+        Ok(Self {
+            namespace,
+            binding,
+            prefix: prefix.to_string(),
+            shard_count: check_shard_count(shard_count)?,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// The number of shards keys are spread over.
+    pub fn shard_count(&self) -> u32 {
+        self.shard_count
+    }
+
+    /// Returns the index of the shard that owns `key`.
+    pub fn shard_index(&self, key: impl AsRef<[u8]>) -> u32 {
+        shard_for(key.as_ref(), self.shard_count)
+    }
+
+    /// Returns the object name of the shard at `index`.
+    pub fn shard_name(&self, index: u32) -> String {
+        format!("{}-{}", self.prefix, index)
+    }
+
+    /// Get a proxy to the shard that owns `key`.
+    ///
+    /// ```ignore
+    /// let shard = counters.get(user_id)?;
+    /// ```
+    pub fn get(&self, key: impl AsRef<[u8]>) -> Result<Proxy<O>, worker::Error> {
+        self.shard(self.shard_index(key))
+    }
+
+    /// Get a proxy to the shard at `index`.
+    pub fn shard(&self, index: u32) -> Result<Proxy<O>, worker::Error> {
+        let transport = named_transport(&self.namespace, &self.shard_name(index), O::OBJ_OPTIONS)?;
+
+        Ok(Proxy::with_transport(transport, self.binding.clone()))
+    }
+
+    /// Returns a sharded proxy over the same prefix with a different number of
+    /// shards.
+    ///
+    /// The shard is chosen with a jump consistent hash, so growing from `n` to
+    /// `m` shards only moves about `1 - n / m` of the keys, and every key that
+    /// moves, moves to one of the new shards. Use [`ShardedProxy::moved`] to find
+    /// the keys that have to be migrated.
+    ///
+    /// Fails if `shard_count` is zero.
+    pub fn resharded(self, shard_count: u32) -> Result<Self, worker::Error> {
+        Self::new(self.namespace, self.binding, &self.prefix, shard_count)
+    }
+
+    /// Returns `Some((from, to))` if `key` is owned by a different shard when
+    /// the shard count changes from `previous_shard_count` to the current one.
+    ///
+    /// Fails if `previous_shard_count` is zero.
+    pub fn moved(
+        &self,
+        key: impl AsRef<[u8]>,
+        previous_shard_count: u32,
+    ) -> Result<Option<(u32, u32)>, worker::Error> {
+        let key = key.as_ref();
+        let from = shard_for(key, check_shard_count(previous_shard_count)?);
+        let to = shard_for(key, self.shard_count);
+
+        Ok((from != to).then_some((from, to)))
+    }
+
+    /// Calls `f` with a proxy to every shard, one shard at a time, and collects
+    /// the results. This is meant for administrative tasks like migrations or
+    /// collecting statistics from all shards.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let totals = counters
+    ///     .for_each_shard(|_index, shard| async move { shard.send(Command::Total).await })
+    ///     .await?;
+    /// ```
+    pub async fn for_each_shard<F, Fut, T>(&self, mut f: F) -> Result<Vec<T>, worker::Error>
+    where
+        F: FnMut(u32, Proxy<O>) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let mut results = Vec::with_capacity(self.shard_count as usize);
+        for index in 0..self.shard_count {
+            results.push(f(index, self.shard(index)?).await);
+        }
+
+        Ok(results)
+    }
+}
+
+/// Returns `shard_count` if there is at least one shard.
+fn check_shard_count(shard_count: u32) -> Result<u32, worker::Error> {
+    match shard_count {
+        0 => Err(worker::Error::from(
+            "a sharded proxy needs at least one shard",
+        )),
+        shard_count => Ok(shard_count),
+    }
+}
+
+/// Maps `key` onto one of `shard_count` shards.
+///
+/// The key is hashed with 64-bit FNV-1a, which is stable across platforms and
+/// compiler versions, and the hash is mapped to a shard with the jump
+/// consistent hash from Lamping and Veach, "A Fast, Minimal Memory, Consistent
+/// Hash Algorithm".
+fn shard_for(key: &[u8], shard_count: u32) -> u32 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in key {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    let mut bucket: i64 = -1;
+    let mut next: i64 = 0;
+    while next < i64::from(shard_count) {
+        bucket = next;
+        hash = hash.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+        next = ((bucket + 1) as f64 * ((1u64 << 31) as f64 / ((hash >> 33) + 1) as f64)) as i64;
+    }
+
+    bucket as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> impl Iterator<Item = String> {
+        (0..10_000).map(|index| format!("key-{index}"))
+    }
+
+    #[test]
+    fn assignments_are_stable() {
+        // Changing these would send keys to other shards than callers built
+        // with an earlier release.
+        let pinned = [
+            ("", 13, 266),
+            ("a", 12, 163),
+            ("/index.html", 15, 127),
+            ("user:42", 15, 295),
+        ];
+
+        for (key, of_16, of_1000) in pinned {
+            assert_eq!(shard_for(key.as_bytes(), 1), 0);
+            assert_eq!(shard_for(key.as_bytes(), 16), of_16, "{key:?} of 16");
+            assert_eq!(shard_for(key.as_bytes(), 1000), of_1000, "{key:?} of 1000");
+        }
+    }
+
+    #[test]
+    fn zero_shards_are_rejected() {
+        assert!(check_shard_count(0).is_err());
+        assert_eq!(check_shard_count(1).unwrap(), 1);
+    }
+
+    #[test]
+    fn assignments_are_in_range() {
+        for shard_count in [1, 2, 7, 16] {
+            assert!(keys().all(|key| shard_for(key.as_bytes(), shard_count) < shard_count));
+        }
+    }
+
+    #[test]
+    fn growing_only_moves_keys_to_new_shards() {
+        for (from, to) in [(1, 2), (4, 5), (8, 16), (10, 13)] {
+            let mut moved = 0;
+            for key in keys() {
+                let before = shard_for(key.as_bytes(), from);
+                let after = shard_for(key.as_bytes(), to);
+
+                if before != after {
+                    assert!(
+                        after >= from,
+                        "{key} moved from {before} to old shard {after}"
+                    );
+                    moved += 1;
+                }
+            }
+
+            // About `1 - from / to` of the keys move.
+            let expected = 10_000.0 * (1.0 - f64::from(from) / f64::from(to));
+            assert!(
+                (f64::from(moved) - expected).abs() < 10_000.0 * 0.03,
+                "{moved} of 10000 keys moved from {from} to {to} shards, expected about {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn keys_are_spread_evenly() {
+        let mut counts = [0u32; 16];
+        for key in keys() {
+            counts[shard_for(key.as_bytes(), 16) as usize] += 1;
+        }
+
+        // 625 keys per shard on average.
+        assert!(
+            counts.iter().all(|&count| (500..750).contains(&count)),
+            "{counts:?}"
+        );
+    }
+}
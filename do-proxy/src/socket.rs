@@ -0,0 +1,304 @@
+use std::{cell::RefCell, collections::HashMap, marker::PhantomData, rc::Rc};
+
+use futures::{future, Stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use worker::{
+    Env, Headers, Method, Request, RequestInit, Response, State, WebSocket, WebSocketPair,
+    WebsocketEvent,
+};
+
+use crate::{
+    auth::{ObjectRef, Signer},
+    codec,
+    glue::{cached_or_load, ObjectSlot},
+    proxy::signed_object,
+    proxy_trait::{authenticate, authorize_request, check_size},
+    transport::{EnvelopeVersion, RequestTransport, SocketMessage},
+    Ctx, DoProxy, ObjectApi, Proxy, StubTransport, Transport,
+};
+
+/// Identifies a WebSocket session connected to an object.
+pub type SessionId = u64;
+
+/// A frame received by a [`Session`].
+pub type SessionMessage<O> = SocketMessage<<O as ObjectApi>::Response, <O as ObjectApi>::Error>;
+
+/// The WebSocket sessions connected to an object. Available to the object
+/// through [`Ctx`].
+#[derive(Clone, Default)]
+pub struct Sessions {
+    inner: Rc<RefCell<SessionsInner>>,
+}
+
+#[derive(Default)]
+struct SessionsInner {
+    next_id: SessionId,
+    sockets: HashMap<SessionId, WebSocket>,
+}
+
+impl Sessions {
+    /// The IDs of all connected sessions.
+    pub fn ids(&self) -> Vec<SessionId> {
+        self.inner.borrow().sockets.keys().copied().collect()
+    }
+
+    fn insert(&self, socket: WebSocket) -> SessionId {
+        let mut inner = self.inner.borrow_mut();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.sockets.insert(id, socket);
+        id
+    }
+
+    fn remove(&self, session: SessionId) {
+        self.inner.borrow_mut().sockets.remove(&session);
+    }
+
+    pub(crate) fn send(&self, session: SessionId, frame: &str) -> Result<(), crate::Error> {
+        let socket = self.inner.borrow().sockets.get(&session).cloned();
+        match socket {
+            Some(socket) => Ok(socket.send_with_str(frame)?),
+            None => Err(crate::Error::worker(format!(
+                "no session with id {session}"
+            ))),
+        }
+    }
+
+    pub(crate) fn broadcast(&self, frame: &str) -> Result<(), crate::Error> {
+        let sockets: Vec<_> = self.inner.borrow().sockets.values().cloned().collect();
+        for socket in sockets {
+            socket.send_with_str(frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Serializes an event pushed by an object.
+pub(crate) fn event_frame<E: Serialize>(event: &E) -> Result<String, crate::Error> {
+    serde_json::to_string(&SocketMessage::<&E, ()>::Event { event })
+        .map_err(|err| crate::Error::encode(&err))
+}
+
+/// A typed WebSocket session with an object, created with [`Proxy::connect`].
+///
+/// # Example
+///
+/// ```ignore
+/// let session = env.obj::<Chat>("lobby")?.connect().await?;
+/// session.send(ChatRequest::Join { user })?;
+///
+/// let mut messages = session.messages()?;
+/// while let Some(message) = messages.next().await {
+///     match message? {
+///         SocketMessage::Event { event } => { /* pushed by the object */ }
+///         SocketMessage::Response { response } => { /* reply to a request */ }
+///         _ => {}
+///     }
+/// }
+/// ```
+pub struct Session<O> {
+    socket: WebSocket,
+    object: Option<ObjectRef>,
+    signer: Option<Signer>,
+    _phantom: PhantomData<O>,
+}
+
+impl<O: DoProxy> Session<O> {
+    /// Sends a request to the object. The object's reply arrives through
+    /// [`Session::messages`].
+    pub fn send(&self, request: O::Request) -> Result<(), crate::Error> {
+        // Sessions always speak do-proxy's own envelopes.
+        let envelope = RequestTransport::<O::Init, _>::Request { request };
+        let principal = self
+            .signer
+            .as_ref()
+            .map(|signer| signer.sign_request::<O>(&signed_object(self.object.clone())?, &envelope))
+            .transpose()?;
+        let frame = codec::encode_signed_envelope(
+            EnvelopeVersion::of::<O>(),
+            principal.as_ref(),
+            &envelope,
+        )?;
+        Ok(self.socket.send_with_str(frame)?)
+    }
+
+    /// A stream of the replies and events sent by the object. The stream ends
+    /// when the session is closed.
+    pub fn messages(
+        &self,
+    ) -> Result<impl Stream<Item = Result<SessionMessage<O>, crate::Error>> + '_, crate::Error>
+    {
+        let events = self.socket.events()?;
+
+        Ok(events
+            .scan((), |_, event| {
+                // Returning `None` ends the stream, `Some(None)` skips the event.
+                future::ready(match event {
+                    Ok(WebsocketEvent::Message(message)) => Some(message.text().map(|text| {
+                        serde_json::from_str(&text).map_err(|err| crate::Error::decode(&err, &text))
+                    })),
+                    Ok(WebsocketEvent::Close(_)) => None,
+                    Err(error) => Some(Some(Err(error.into()))),
+                })
+            })
+            .filter_map(future::ready))
+    }
+
+    /// Closes the session.
+    pub fn close(self) -> Result<(), crate::Error> {
+        Ok(self.socket.close(Some(1000), Some("session closed"))?)
+    }
+}
+
+impl<O: DoProxy> Proxy<O, StubTransport> {
+    /// Opens a WebSocket session with the object. The object handles requests
+    /// sent over the session in [`DoProxy::on_socket_message`].
+    ///
+    /// ```ignore
+    /// let session = proxy.connect().await?;
+    /// ```
+    pub async fn connect(&self) -> Result<Session<O>, crate::Error> {
+        let mut headers = Headers::new();
+        headers.set("upgrade", "websocket")?;
+
+        let mut request_init = RequestInit::new();
+        request_init.with_method(Method::Get).with_headers(headers);
+
+        let request =
+            Request::new_with_init(&format!("http://{}/", self.binding()), &request_init)?;
+        let response = self.transport().stub().fetch_with_request(request).await?;
+        let socket = response
+            .websocket()
+            .ok_or_else(|| crate::Error::worker("the object did not accept the WebSocket"))?;
+        socket.accept()?;
+
+        Ok(Session {
+            socket,
+            object: self.transport().object(),
+            signer: self.signer().cloned(),
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// Returns `true` if `req` asks to open a WebSocket session.
+pub fn is_upgrade(req: &Request) -> bool {
+    matches!(req.headers().get("upgrade"), Ok(Some(upgrade)) if upgrade.eq_ignore_ascii_case("websocket"))
+}
+
+/// Accepts a WebSocket session and handles its frames until it is closed.
+pub fn accept<O: DoProxy + 'static>(
+    object: Rc<ObjectSlot<O>>,
+    state: Rc<State>,
+    env: Rc<Env>,
+    sessions: Sessions,
+) -> worker::Result<Response> {
+    let WebSocketPair { client, server } = WebSocketPair::new()?;
+    server.accept()?;
+    let session = sessions.insert(server.clone());
+
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Ok(mut events) = server.events() {
+            while let Some(Ok(WebsocketEvent::Message(message))) = events.next().await {
+                let Some(text) = message.text() else {
+                    continue;
+                };
+
+                let mut ctx = Ctx::new(&state, &env)
+                    .with_sessions(&sessions)
+                    .with_session(session);
+                if let Some(frame) = handle_frame(&object, &mut ctx, session, &text).await {
+                    let _ = server.send_with_str(frame);
+                }
+            }
+        }
+
+        sessions.remove(session);
+        let mut ctx = Ctx::new(&state, &env).with_sessions(&sessions);
+        let mut cached = object.lock().await;
+        if let Ok(proxy) = cached_or_load(&mut cached, &mut ctx).await {
+            proxy.on_socket_close(&mut ctx, session).await;
+            if ctx.flush_writes().await.is_err() {
+                // The cached object may not match storage anymore.
+                *cached = None;
+            }
+        }
+    });
+
+    Response::from_websocket(client)
+}
+
+async fn handle_frame<O: DoProxy>(
+    object: &ObjectSlot<O>,
+    ctx: &mut Ctx<'_>,
+    session: SessionId,
+    text: &str,
+) -> Option<String> {
+    let decoded = check_size::<O>(text.len())
+        .and_then(|()| codec::decode_request_with::<O>(text, O::upcast_request))
+        .and_then(|decoded| {
+            let signed = decoded.principal.as_ref().zip(decoded.digest.as_deref());
+            ctx.set_principal(authenticate::<O>(ctx, signed)?);
+            Ok(decoded)
+        });
+    let (api_version, request) = match decoded {
+        Ok(codec::DecodedRequest {
+            version,
+            envelope: RequestTransport::Request { request },
+            ..
+        }) => (version.api_version, request),
+        Ok(_) => {
+            let error = crate::Error::ProtocolMismatch {
+                expected: "a request frame".to_string(),
+                found: "an init frame".to_string(),
+            };
+            return failure_frame::<O>(error);
+        }
+        Err(error) => return failure_frame::<O>(error),
+    };
+
+    let checked = match authorize_request::<O>(ctx, &request).await {
+        Ok(()) => O::validate_request(&request).map_err(crate::Error::from),
+        Err(error) => Err(error),
+    };
+    if let Err(error) = checked {
+        return failure_frame::<O>(error);
+    }
+
+    let mut cached = object.lock().await;
+    let proxy = match cached_or_load(&mut cached, ctx).await {
+        Ok(proxy) => proxy,
+        Err(error) => return reply_frame::<O>(SocketMessage::Error { error }),
+    };
+
+    let outcome = proxy.on_socket_message(ctx, session, request).await;
+    if outcome.is_err() {
+        ctx.discard_writes();
+    } else if let Err(error) = ctx.flush_writes().await {
+        // The cached object may not match storage anymore.
+        *cached = None;
+        return failure_frame::<O>(error);
+    }
+    drop(cached);
+
+    let reply = match outcome {
+        Ok(Some(response)) => match O::downcast_response(api_version, response) {
+            Ok(response) => Some(SocketMessage::Response { response }),
+            Err(error) => Some(SocketMessage::Failure { error }),
+        },
+        Ok(None) => None,
+        Err(error) => Some(SocketMessage::Error { error }),
+    };
+
+    reply.and_then(reply_frame::<O>)
+}
+
+fn failure_frame<O: DoProxy>(error: crate::Error) -> Option<String> {
+    reply_frame::<O>(SocketMessage::Failure { error })
+}
+
+fn reply_frame<O: DoProxy>(frame: SocketMessage<Value, O::Error>) -> Option<String> {
+    serde_json::to_string(&frame).ok()
+}
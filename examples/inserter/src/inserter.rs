@@ -1,4 +1,4 @@
-use do_proxy::{async_trait, do_proxy, DoProxy, ProxiedRequest};
+use do_proxy::{async_trait, do_proxy, DoProxy, ObjectApi, ProxiedRequest, Validate};
 use serde::{Deserialize, Serialize};
 
 pub struct Inserter;
@@ -18,6 +18,8 @@ pub enum InserterRequest {
     },
 }
 
+impl Validate for InserterRequest {}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub enum InserterResponse {
@@ -26,15 +28,17 @@ pub enum InserterResponse {
     Value(Option<serde_json::Value>),
 }
 
-#[async_trait(?Send)]
-impl DoProxy for Inserter {
+impl ObjectApi for Inserter {
     const BINDING: &'static str = "INSERTER_OBJECT";
 
     type Init = ();
     type Request = InserterRequest;
     type Response = InserterResponse;
     type Error = do_proxy::Error;
+}
 
+#[async_trait(?Send)]
+impl DoProxy for Inserter {
     async fn load_from_storage(_ctx: &mut do_proxy::Ctx) -> Result<Self, Self::Error> {
         Ok(Self)
     }
@@ -47,15 +51,15 @@ impl DoProxy for Inserter {
         match req {
             ProxiedRequest::Fetch(req) => match req {
                 InserterRequest::Insert { key, value } => {
-                    ctx.state.storage().put(&key, &value).await?;
+                    ctx.put(key, &value)?;
                     Ok(InserterResponse::Inserted)
                 }
                 InserterRequest::Get { key } => {
-                    let value = ctx.state.storage().get(&key).await.ok();
+                    let value = ctx.get(&key).await?;
                     Ok(InserterResponse::Value(value))
                 }
                 InserterRequest::Delete { key } => {
-                    ctx.state.storage().delete(&key).await?;
+                    ctx.delete(key);
                     Ok(InserterResponse::Deleted)
                 }
             },
@@ -2,24 +2,19 @@ mod inserter;
 
 use self::inserter::Inserter;
 
-use do_proxy::EnvExt;
+use do_proxy::Gateway;
 use worker::*;
 
 /// A simple pass-through worker that forwards commands to the given durable
 /// object and returns its response.
+///
+/// A `POST` to `/{name}` sends the command in the body to the `Inserter` named
+/// `/{name}`, the name this worker gave objects before it used a [`Gateway`].
+/// Other methods are rejected with `405 Method Not Allowed`.
 #[worker::event(fetch, respond_with_errors)]
-pub async fn main(mut req: Request, env: Env, _ctx: worker::Context) -> Result<Response> {
-    let url = req.url()?;
-    let do_name = url.path();
-
-    // Deserialize a command from the request
-    let command = req.json().await?;
-
-    // Easily access an `Inserter` object with the given name.
-    let inserter = env.obj::<Inserter>(do_name)?;
-
-    // Send the command to the object.
-    let resp = inserter.send(command).await?;
-
-    Response::from_json(&resp)
+pub async fn main(req: Request, env: Env, _ctx: worker::Context) -> Result<Response> {
+    Gateway::new()
+        .object_with::<Inserter>("", |name| format!("/{name}"))
+        .handle(req, &env)
+        .await
 }